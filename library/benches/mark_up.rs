@@ -0,0 +1,29 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use library::definitions::Window;
+use library::functions;
+
+// roughly 400k words, about the length of a long novel.
+fn novel() -> String {
+    std::fs::read_to_string("../test_files/test2.txt")
+        .expect("Could not read test2.txt")
+        .repeat(40)
+}
+
+fn bench_mark_up(c: &mut Criterion) {
+    let word_vec = functions::split_text_into_words(novel()).expect("Could not split the novel");
+    let stop_words = functions::default_stop_words();
+
+    let mut group = c.benchmark_group("mark_up");
+    group.sample_size(10);
+
+    for lookahead in [50, 2000].iter() {
+        group.bench_function(format!("lookahead {}", lookahead), |b| {
+            b.iter(|| functions::mark_up(word_vec.clone(), &stop_words, Window::Words(*lookahead)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_mark_up);
+criterion_main!(benches);
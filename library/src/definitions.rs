@@ -1,9 +1,10 @@
-use anyhow::{bail, Result};
 use docx::DocxError;
+use serde::Serialize;
 use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
 use thiserror::Error;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug)]
 pub enum Source {
@@ -11,69 +12,634 @@ pub enum Source {
     Raw(String),
 }
 
+/// The part of a document a piece of text comes from.
+/// Plain text is all body; a docx file also has tables, headers, footnotes and so on,
+/// and a screenplay is made of scene headings, action, character cues and dialogue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    #[default]
+    Body,
+    Table,
+    TextBox,
+    Header,
+    Footer,
+    Footnote,
+    Endnote,
+    Comment,
+    #[serde(rename = "scene")]
+    SceneHeading,
+    Action,
+    Character,
+    Dialogue,
+    Parenthetical,
+    Transition,
+}
+
+impl Region {
+    /// What's analysed unless asked otherwise. Headers, footers and comments aren't the prose itself,
+    /// and character cues, scene headings and transitions repeat by design.
+    pub const DEFAULT: [Region; 8] = [
+        Region::Body,
+        Region::Table,
+        Region::TextBox,
+        Region::Footnote,
+        Region::Endnote,
+        Region::Action,
+        Region::Dialogue,
+        Region::Parenthetical,
+    ];
+}
+
+impl FromStr for Region {
+    type Err = TonalDistanceError;
+
+    fn from_str(region: &str) -> Result<Self, TonalDistanceError> {
+        match region {
+            "body" => Ok(Region::Body),
+            "table" => Ok(Region::Table),
+            "textbox" => Ok(Region::TextBox),
+            "header" => Ok(Region::Header),
+            "footer" => Ok(Region::Footer),
+            "footnote" => Ok(Region::Footnote),
+            "endnote" => Ok(Region::Endnote),
+            "comment" => Ok(Region::Comment),
+            "scene" => Ok(Region::SceneHeading),
+            "action" => Ok(Region::Action),
+            "character" => Ok(Region::Character),
+            "dialogue" => Ok(Region::Dialogue),
+            "parenthetical" => Ok(Region::Parenthetical),
+            "transition" => Ok(Region::Transition),
+            _ => Err(TonalDistanceError::ParseError {
+                kind: "region",
+                value: region.to_owned(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Region::Body => "body",
+            Region::Table => "table",
+            Region::TextBox => "textbox",
+            Region::Header => "header",
+            Region::Footer => "footer",
+            Region::Footnote => "footnote",
+            Region::Endnote => "endnote",
+            Region::Comment => "comment",
+            Region::SceneHeading => "scene",
+            Region::Action => "action",
+            Region::Character => "character",
+            Region::Dialogue => "dialogue",
+            Region::Parenthetical => "parenthetical",
+            Region::Transition => "transition",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A paragraph of a document, tagged with where it comes from.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Section {
+    pub text: String,
+    pub region: Region,
+    pub chapter: Option<u32>,    // for books, counting from zero
+    pub speaker: Option<String>, // for a screenplay's dialogue
+    pub file: Option<PathBuf>,   // when several files are read as one
+    pub verbatim: bool,          // its lines are its file's own, not text extracted from it
+}
+
+/// A document as read from a file. Its sections are analysed as one text, a line each.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Document {
+    pub sections: Vec<Section>,
+}
+
+impl Document {
+    pub fn text(&self) -> String {
+        self.sections
+            .iter()
+            .map(|section| section.text.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    /// Marks the text as extracted from its file, so its lines and columns aren't the file's.
+    pub fn extracted(self) -> Document {
+        Document {
+            sections: self
+                .sections
+                .into_iter()
+                .map(|section| Section {
+                    verbatim: false,
+                    ..section
+                })
+                .collect(),
+        }
+    }
+
+    /// Keeps only the sections in `regions`.
+    pub fn only(self, regions: &[Region]) -> Document {
+        Document {
+            sections: self
+                .sections
+                .into_iter()
+                .filter(|section| regions.contains(&section.region))
+                .collect(),
+        }
+    }
+}
+
+/// Plain text is all body, a section per line.
+impl From<String> for Document {
+    fn from(s: String) -> Self {
+        Document {
+            sections: s
+                .split('\n')
+                .map(|line| Section {
+                    text: line.to_owned(),
+                    verbatim: true,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ResponseType {
     Raw,
     // Colorized,
     Formatted,
+    Pairs,
+    Json,
+    Html,
+    Sarif,
+    Csv,
+    Tsv,
+}
+
+/// The unit the lookahead is measured in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowUnit {
+    Words,
+    Characters,
+    Sentences,
+    Paragraphs,
+}
+
+impl FromStr for WindowUnit {
+    type Err = TonalDistanceError;
+
+    fn from_str(unit: &str) -> Result<Self, TonalDistanceError> {
+        match unit {
+            "words" => Ok(WindowUnit::Words),
+            "characters" => Ok(WindowUnit::Characters),
+            "sentences" => Ok(WindowUnit::Sentences),
+            "paragraphs" => Ok(WindowUnit::Paragraphs),
+            _ => Err(TonalDistanceError::ParseError {
+                kind: "window unit",
+                value: unit.to_owned(),
+            }),
+        }
+    }
+}
+
+/// How far ahead to look for a repetition.
+/// `Sentences(0)` only looks within the same sentence, `Sentences(1)` also in the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Words(usize),
+    Characters(usize),
+    Sentences(usize),
+    Paragraphs(usize),
+}
+
+impl Window {
+    pub fn new(unit: WindowUnit, length: usize) -> Window {
+        match unit {
+            WindowUnit::Words => Window::Words(length),
+            WindowUnit::Characters => Window::Characters(length),
+            WindowUnit::Sentences => Window::Sentences(length),
+            WindowUnit::Paragraphs => Window::Paragraphs(length),
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        match *self {
+            Window::Words(length)
+            | Window::Characters(length)
+            | Window::Sentences(length)
+            | Window::Paragraphs(length) => length,
+        }
+    }
+}
+
+/// How a text is checked, and how the report comes back.
+#[derive(Debug)]
+pub struct Settings {
+    pub window: Window,
+    pub stemmer: Stemmer,
+    /// Also look for repeated phrases of 2 up to this many words.
+    pub phrases: Option<usize>,
+    /// Repetitions less severe than this aren't reported.
+    pub min_severity: u32,
+    /// Check each speaker's lines only against their own.
+    pub per_speaker: bool,
+    pub response: ResponseType,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            window: Window::Words(50),
+            stemmer: Stemmer::None,
+            phrases: None,
+            min_severity: 0,
+            per_speaker: false,
+            response: ResponseType::Formatted,
+        }
+    }
+}
+
+/// Reduces a word to the form it is compared by, e.g. "frowning" to "frown".
+pub trait Normalize {
+    fn normalize(&self, word: &str) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stemmer {
+    None,
+    English,
+}
+
+impl FromStr for Stemmer {
+    type Err = TonalDistanceError;
+
+    fn from_str(stemmer: &str) -> Result<Self, TonalDistanceError> {
+        match stemmer {
+            "none" => Ok(Stemmer::None),
+            "english" | "porter" | "snowball" => Ok(Stemmer::English),
+            _ => Err(TonalDistanceError::ParseError {
+                kind: "stemmer",
+                value: stemmer.to_owned(),
+            }),
+        }
+    }
+}
+
+impl Stemmer {
+    /// The normalizer to run a text through, made once for all its words.
+    pub fn normalizer(&self) -> Box<dyn Normalize> {
+        match self {
+            Stemmer::None => Box::new(Unchanged),
+            Stemmer::English => Box::new(rust_stemmers::Stemmer::create(
+                rust_stemmers::Algorithm::English,
+            )),
+        }
+    }
+}
+
+/// Leaves words as they are.
+pub struct Unchanged;
+
+impl Normalize for Unchanged {
+    fn normalize(&self, word: &str) -> String {
+        word.to_owned()
+    }
+}
+
+impl Normalize for rust_stemmers::Stemmer {
+    fn normalize(&self, word: &str) -> String {
+        self.stem(word).into_owned()
+    }
+}
+
+/// When to colour the repetitions in a report for the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    /// Only when writing to a terminal, and NO_COLOR isn't set.
+    #[default]
+    Auto,
+}
+
+impl ColorChoice {
+    pub fn enabled(&self, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                is_terminal && std::env::var_os("NO_COLOR").map_or(true, |value| value.is_empty())
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = TonalDistanceError;
+
+    fn from_str(choice: &str) -> Result<Self, TonalDistanceError> {
+        match choice {
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            "auto" => Ok(ColorChoice::Auto),
+            _ => Err(TonalDistanceError::ParseError {
+                kind: "color choice",
+                value: choice.to_owned(),
+            }),
+        }
+    }
+}
+
+/// How the bytes of a text file are decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextEncoding {
+    /// Guess from a byte order mark, then from the bytes themselves,
+    /// falling back to Windows-1252 for anything that isn't UTF-8 or UTF-16.
+    #[default]
+    Detect,
+    /// Any encoding with a WHATWG label, like "windows-1252", "utf-16le" or "shift_jis".
+    Given(&'static encoding_rs::Encoding),
+}
+
+impl FromStr for TextEncoding {
+    type Err = TonalDistanceError;
+
+    fn from_str(encoding: &str) -> Result<Self, TonalDistanceError> {
+        if encoding.eq_ignore_ascii_case("auto") {
+            return Ok(TextEncoding::Detect);
+        }
+        match encoding_rs::Encoding::for_label(encoding.as_bytes()) {
+            Some(given) => Ok(TextEncoding::Given(given)),
+            None => Err(TonalDistanceError::ParseError {
+                kind: "text encoding",
+                value: encoding.to_owned(),
+            }),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum Response {
     VecOfRuns(Vec<Run>),
+    VecOfPairs(Vec<Pair>),
+    Json(JsonReport),
     Str(String),
 }
 
+/// The machine-readable report: every flagged word, and the runs to render the text with.
+/// `tdist --response json` and the server both return this, so its shape is kept stable:
+/// fields only the library needs, like a word's stem, aren't serialized.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct JsonReport {
+    pub words: Vec<Word>,
+    pub runs: Vec<Run>,
+}
+
 impl FromStr for ResponseType {
-    type Err = anyhow::Error;
+    type Err = TonalDistanceError;
 
-    fn from_str(res_type: &str) -> Result<Self, anyhow::Error> {
+    fn from_str(res_type: &str) -> Result<Self, TonalDistanceError> {
         match res_type {
             "raw" => Ok(ResponseType::Raw),
             // "colorized" => Ok(ResponseType::Colorized),
             "formatted" => Ok(ResponseType::Formatted),
-            _ => bail!("Could not parse a response type"),
+            "pairs" => Ok(ResponseType::Pairs),
+            "json" => Ok(ResponseType::Json),
+            "html" => Ok(ResponseType::Html),
+            "sarif" => Ok(ResponseType::Sarif),
+            "csv" => Ok(ResponseType::Csv),
+            "tsv" => Ok(ResponseType::Tsv),
+            _ => Err(TonalDistanceError::ParseError {
+                kind: "response type",
+                value: res_type.to_owned(),
+            }),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Another occurrence of the same word, found within the lookahead.
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct Partner {
+    pub word_position: u32,
+    pub distance: u32, // in words
+    pub severity: u32, // 0 to 100
+}
+
+/// A run of words that is repeated as a whole, e.g. "for a moment".
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
+pub struct Phrase {
+    pub start: u32,  // word position of the first word
+    pub length: u32, // in words
+    pub partners: Vec<Partner>,
+}
+
+impl Phrase {
+    /// The worst of its repetitions, like a word's severity.
+    pub fn severity(&self) -> u32 {
+        self.partners.iter().map(|p| p.severity).max().unwrap_or(0)
+    }
+
+    pub fn represent(&self, text: &str, first: &Word) -> String {
+        format!(
+            "{}{}Line: {}Word Position: {}Repeats: {}",
+            pad(&format!("Phrase: {}", text), TEXT_COLUMN),
+            pad(&first.location(), LOCATION_COLUMN),
+            pad(&first.line_and_column(), NUMBER_COLUMN),
+            pad(&(self.start + 1).to_string(), NUMBER_COLUMN),
+            represent_partners(&self.partners)
+        )
+    }
+}
+
+// the widths of the formatted report's columns, shared by its word and phrase rows.
+const TEXT_COLUMN: usize = 27; // "Word: snow" or "Phrase: for a moment"
+const LOCATION_COLUMN: usize = 31;
+const NUMBER_COLUMN: usize = 20;
+
+// pads a column to its width on screen, leaving at least one space,
+// so neither long words nor wide characters throw out the columns after it.
+fn pad(text: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(width.saturating_sub(text.width()).max(1))
+    )
+}
+
+fn represent_partners(partners: &[Partner]) -> String {
+    partners
+        .iter()
+        .map(|partner| {
+            format!(
+                "{} ({} words away)",
+                partner.word_position + 1,
+                partner.distance
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// A word of the text, and what was found about it.
+///
+/// Its byte offsets, line and column are into the text it was split from: for a document,
+/// `Document::text()`, after decoding, extracting and joining. That's the file's own text only
+/// for a single UTF-8 plain-text file; for a docx, odt, epub, html, rtf, Markdown or Fountain file,
+/// a UTF-16 or Windows-1252 one, or one of several files, use its file, paragraph and location instead.
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
 pub struct Word {
     pub pure_word: String,     // sub
     pub original_word: String, // Sub!"
+    #[serde(skip)]
+    pub stem: Option<String>, // only set once a stemmer has run
     pub repeated: bool,
     pub word_position: u32,
     pub paragraph: u32,
+    #[serde(skip)]
+    pub sentence: u32,
+    pub region: Region,
+    pub chapter: Option<u32>,
+    #[serde(skip)]
+    pub chapter_paragraph: u32, // counted from the start of its chapter
+    pub speaker: Option<String>,
+    pub file: Option<PathBuf>,
+    #[serde(skip)]
+    pub file_line: u32, // counted from the start of its file
+    #[serde(skip)]
+    pub verbatim: bool, // whether its line and column are in its file, or in text extracted from it
+    pub byte_start: usize, // of pure_word in the text it was split from
+    pub byte_end: usize,
+    pub line: u32,
+    pub column: u32, // in characters
+    pub partners: Vec<Partner>,
+    pub phrase: Option<Phrase>,
+    pub severity: u32, // the worst of its partners
 }
 
 impl Word {
+    /// What this word is compared by when looking for repetitions.
+    pub fn key(&self) -> &str {
+        self.stem.as_deref().unwrap_or(&self.pure_word)
+    }
+
+    /// e.g. "Paragraph: 12", "Paragraph: 3 (footnote)", "Paragraph: 40 (dialogue: JOHN)",
+    /// or "Chapter 7, paragraph 12" in a book.
+    pub fn location(&self) -> String {
+        let location = match self.chapter {
+            Some(chapter) => format!(
+                "Chapter {}, paragraph {}",
+                chapter + 1,
+                self.chapter_paragraph + 1
+            ),
+            None => format!("Paragraph: {}", self.paragraph + 1),
+        };
+        match (self.region, &self.speaker) {
+            (Region::Body, _) => location,
+            (region, Some(speaker)) => format!("{} ({}: {})", location, region, speaker),
+            (region, None) => format!("{} ({})", location, region),
+        }
+    }
+
+    /// e.g. "3:14", or "chapter2.txt:3:14" when several files are read as one,
+    /// counting from one like an editor does. Text extracted from a file, like a docx file's,
+    /// has no lines of the file's own, so it's only the file, or "-".
+    pub fn line_and_column(&self) -> String {
+        match (&self.file, self.verbatim) {
+            (Some(file), true) => format!(
+                "{}:{}:{}",
+                file.display(),
+                self.line_number(),
+                self.column + 1
+            ),
+            (None, true) => format!("{}:{}", self.line_number(), self.column + 1),
+            (Some(file), false) => file.display().to_string(),
+            (None, false) => String::from("-"),
+        }
+    }
+
+    /// The line it's on, in its own file when there are several, counting from one.
+    pub fn line_number(&self) -> u32 {
+        match self.file {
+            Some(_) => self.file_line + 1,
+            None => self.line + 1,
+        }
+    }
+
     pub fn represent(&self) -> String {
-        let word_buff = vec![' '; (20 - self.pure_word.len() as i32).abs() as usize]
-            .into_iter()
-            .collect::<String>();
+        format!(
+            "{}{}Line: {}Word Position: {}Severity: {}Repeats: {}",
+            pad(
+                &format!("Word: {}", self.original_word.trim_end()),
+                TEXT_COLUMN
+            ),
+            pad(&self.location(), LOCATION_COLUMN),
+            pad(&self.line_and_column(), NUMBER_COLUMN),
+            pad(&(self.word_position + 1).to_string(), NUMBER_COLUMN),
+            pad(&self.severity.to_string(), NUMBER_COLUMN),
+            represent_partners(&self.partners)
+        )
+    }
+}
+
+/// Two occurrences of the same word, the second within the lookahead of the first.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Pair {
+    pub first: Word,
+    pub second: Word,
+    pub distance: u32, // in words
+    pub severity: u32,
+}
 
-        let paragraph_buff = vec![' '; 20 - self.paragraph.to_string().len()]
-            .into_iter()
-            .collect::<String>();
+// the file and line too, when there are several files to tell apart.
+fn pair_location(word: &Word) -> String {
+    match (&word.file, word.verbatim) {
+        (Some(_), true) => format!("{}, Line: {}", word.location(), word.line_and_column()),
+        (Some(file), false) => format!("{}, File: {}", word.location(), file.display()),
+        (None, _) => word.location(),
+    }
+}
+
+impl Pair {
+    pub fn represent(&self) -> String {
         format!(
-            "Word: {}{}Paragraph: {}{}Word Position: {}",
-            self.original_word,
-            word_buff,
-            self.paragraph + 1,
-            paragraph_buff,
-            self.word_position + 1
+            "Word: {} ({}, Word Position: {})  Repeated: {} ({}, Word Position: {})  Distance: {}  Severity: {}",
+            self.first.original_word.trim_end(),
+            pair_location(&self.first),
+            self.first.word_position + 1,
+            self.second.original_word.trim_end(),
+            pair_location(&self.second),
+            self.second.word_position + 1,
+            self.distance,
+            self.severity
         )
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// A stretch of the text to render. Each repeated word or phrase is a run of its own,
+/// so it can be told apart from the others and linked to its partners.
+/// Its offsets, line and column are its words', in the same text as theirs.
+#[derive(Debug, PartialEq, Clone, Default, Serialize)]
 pub struct Run {
     pub text: String,
     pub repeated: bool,
+    pub phrase: bool,
+    pub severity: u32,     // its word's, or its phrase's
+    pub byte_start: usize, // of its first word
+    pub byte_end: usize,   // of its last word
+    pub line: u32,
+    pub column: u32,
+    pub key: Option<String>, // what it's compared by, shared by its word family
+    pub word_position: Option<u32>, // of its first word, if it's repeated
+    pub partner_positions: Vec<u32>, // of the words or phrases it repeats
 }
 
 #[derive(Error, Debug)]
-pub struct DocError(DocxError);
+pub struct DocError(pub DocxError);
 
 impl fmt::Display for DocError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -92,9 +658,47 @@ pub enum TonalDistanceError {
     #[error("Failed to read from docx file")]
     DocXReadError { source: DocError },
 
-    /// Should not occur... ;)
-    #[error("Oops!")]
-    GenericError,
+    /// Represents a failure to open a zipped document, like an odt file.
+    #[error("Failed to read from the archive")]
+    ZipReadError { source: zip::result::ZipError },
+
+    /// Represents a failure to write a zipped document, like an annotated docx file.
+    #[error("Failed to write the archive")]
+    ZipWriteError { source: zip::result::ZipError },
+
+    /// Represents a document whose XML we couldn't parse.
+    #[error("Failed to parse the document's XML")]
+    XmlReadError { source: roxmltree::Error },
+
+    /// Represents a file that is the right format on the outside, but not on the inside.
+    #[error("Could not make sense of the {format} file: {reason}")]
+    MalformedFile {
+        format: &'static str,
+        reason: &'static str,
+    },
+
+    /// Represents a path or glob pattern that names no file we can read.
+    #[error("No files match {pattern}")]
+    NoMatches { pattern: String },
+
+    /// Represents a file without an extension, so we can't tell how to read it.
+    #[error("Please specify the file extension of {path:?}")]
+    MissingExtension { path: PathBuf },
+
+    /// Represents a file we can't read as text.
+    #[error("Unsupported file format: {extension}")]
+    UnsupportedFormat { extension: String },
+
+    /// Represents a failure to read a stop-word list.
+    #[error("Could not read the stop words file {path:?}")]
+    StopWordsError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    /// Represents an option we don't recognise, like an unknown response type.
+    #[error("Could not parse a {kind}: {value}")]
+    ParseError { kind: &'static str, value: String },
 
     /// Represents all other cases of `std::io::Error`.
     #[error(transparent)]
@@ -1,10 +1,14 @@
 use crate::definitions::*;
-use anyhow::Result;
 use colored::*;
 
-use docx::{document::BodyContent, DocxFile};
+use docx::{
+    document::{BodyContent, Paragraph, TableCellContent},
+    DocxFile,
+};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 use regex::Regex;
-use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
 use std::{fs, path::PathBuf};
 
 pub fn split_text_into_words(s: String) -> Result<Vec<Word>, TonalDistanceError> {
@@ -17,13 +21,30 @@ pub fn split_text_into_words(s: String) -> Result<Vec<Word>, TonalDistanceError>
 
     // let's track paragraph for fun
     let mut paragraph_count: u32 = 0;
+    // and sentences, which end at terminal punctuation or at the end of a paragraph.
+    let mut sentence_count: u32 = 0;
+    // and where we are, so editors can find each word again.
+    let mut cursor: usize = 0;
+    let mut line: u32 = 0;
+    let mut column: u32 = 0;
 
     let split_words = re
         .captures_iter(&s)
         .map(|preword| {
             // 0 is the whole capture, 1 is the first bracket.
             let original_word = preword.get(0).expect("No capture found").as_str();
-            let pre_pure_word = preword.get(1).expect("No capture found").as_str();
+            let pure_match = preword.get(1).expect("No capture found");
+            let pre_pure_word = pure_match.as_str();
+
+            for ch in s[cursor..pure_match.start()].chars() {
+                if ch == '\n' {
+                    line += 1;
+                    column = 0;
+                } else {
+                    column += 1;
+                }
+            }
+            cursor = pure_match.start();
 
             // we want to iterate paragraph count without borrowing this later, but we also want to be accurate about current paragraph.
             let mut accounting = 0;
@@ -32,10 +53,19 @@ pub fn split_text_into_words(s: String) -> Result<Vec<Word>, TonalDistanceError>
                 accounting = 1;
             }
 
+            let mut sentence_accounting = 0;
+            if original_word[pre_pure_word.len()..].contains(&['.', '!', '?', '\n'][..]) {
+                sentence_count += 1;
+                sentence_accounting = 1;
+            }
+
             (
                 String::from(pre_pure_word.to_lowercase()),
                 original_word,
                 paragraph_count - accounting,
+                sentence_count - sentence_accounting,
+                pure_match.range(),
+                (line, column),
             )
         })
         .enumerate()
@@ -44,183 +74,2563 @@ pub fn split_text_into_words(s: String) -> Result<Vec<Word>, TonalDistanceError>
             original_word: String::from(tupl.1),
             repeated: false,
             paragraph: tupl.2,
+            sentence: tupl.3,
+            region: Region::Body,
+            chapter: None,
+            chapter_paragraph: 0,
+            speaker: None,
+            file: None,
+            file_line: 0,
+            verbatim: true,
+            byte_start: tupl.4.start,
+            byte_end: tupl.4.end,
+            line: (tupl.5).0,
+            column: (tupl.5).1,
             word_position: j as u32,
+            stem: None,
+            partners: vec![],
+            phrase: None,
+            severity: 0,
         })
         .collect::<Vec<Word>>();
 
     Ok(split_words)
 }
 
-pub fn mark_up(v: Vec<Word>, stop_words: Vec<String>, buffer_length: usize) -> Vec<Word> {
-    let mut matches: Vec<u32> = vec![];
+/// Tags each word with the region, chapter, speaker and file of the section it's in,
+/// a section starting on each line.
+pub fn tag_sections(mut v: Vec<Word>, document: &Document) -> Vec<Word> {
+    // a section's text may hold line breaks of its own.
+    let mut starts: Vec<u32> = Vec::with_capacity(document.sections.len());
+    // and the line its file starts on.
+    let mut file_starts: Vec<u32> = Vec::with_capacity(document.sections.len());
+    let mut line: u32 = 0;
+    for (i, section) in document.sections.iter().enumerate() {
+        starts.push(line);
+        file_starts.push(match i {
+            0 => 0,
+            _ if document.sections[i - 1].file == section.file => file_starts[i - 1],
+            _ => line,
+        });
+        line += section.text.matches('\n').count() as u32 + 1;
+    }
 
-    v.clone()
-        .into_iter()
-        .enumerate()
-        .map(|(i, word)| {
-            if stop_words.contains(&word.pure_word) {
-                return word;
-            }
+    // the chapter we're in, and the paragraph it starts on.
+    let mut chapter_start: Option<(u32, u32)> = None;
+    for word in v.iter_mut() {
+        let index = starts.partition_point(|start| *start <= word.line);
+        if index == 0 {
+            continue;
+        }
+        let section = &document.sections[index - 1];
+        word.region = section.region;
+        word.chapter = section.chapter;
+        word.speaker = section.speaker.clone();
+        word.file = section.file.clone();
+        word.file_line = word.line - file_starts[index - 1];
+        word.verbatim = section.verbatim;
 
-            // don't scan beyond the end of the vec
-            let end = if i + buffer_length + 1 > v.len() {
-                v.len()
-            } else {
-                i + buffer_length + 1
+        if let Some(chapter) = section.chapter {
+            let start = match chapter_start {
+                Some((current, start)) if current == chapter => start,
+                _ => {
+                    chapter_start = Some((chapter, word.paragraph));
+                    word.paragraph
+                }
             };
+            word.chapter_paragraph = word.paragraph - start;
+        }
+    }
+    v
+}
 
-            let match_index = v[i + 1..end]
-                .into_iter()
-                .position(|x| x.pure_word == word.pure_word);
-
-            match match_index {
-                Some(matching_index) => {
-                    matches.push((1 + i + matching_index) as u32);
-                    Word {
-                        repeated: true,
-                        ..word
+/// Runs each word through `normalizer`, so that inflected forms share a key.
+pub fn normalize(v: Vec<Word>, normalizer: &dyn Normalize) -> Vec<Word> {
+    v.into_iter()
+        .map(|word| Word {
+            stem: Some(normalizer.normalize(&word.pure_word)),
+            ..word
+        })
+        .collect::<Vec<Word>>()
+}
+
+/// Where each word sits, measured in the window's unit.
+fn window_positions(v: &[Word], window: Window) -> Vec<usize> {
+    match window {
+        Window::Words(_) => v.iter().map(|word| word.word_position as usize).collect(),
+        Window::Characters(_) => v
+            .iter()
+            .scan(0, |chars, word| {
+                let start = *chars;
+                *chars += word.original_word.chars().count();
+                Some(start)
+            })
+            .collect(),
+        Window::Sentences(_) => v.iter().map(|word| word.sentence as usize).collect(),
+        Window::Paragraphs(_) => v.iter().map(|word| word.paragraph as usize).collect(),
+    }
+}
+
+/// Flags every word that recurs within the window, linking each pair of occurrences.
+/// Runs in a single pass by remembering where each word was last seen.
+pub fn mark_up(v: Vec<Word>, stop_words: &HashSet<String>, window: Window) -> Vec<Word> {
+    mark_up_in_lanes(v, stop_words, window, |_| None)
+}
+
+/// Like `mark_up`, but each character's dialogue is only checked against their own,
+/// with the window measured in their words. Everything else is checked as one.
+pub fn mark_up_per_speaker(
+    v: Vec<Word>,
+    stop_words: &HashSet<String>,
+    window: Window,
+) -> Vec<Word> {
+    mark_up_in_lanes(v, stop_words, window, |word| word.speaker.clone())
+}
+
+// the lane each word is in, and where it sits in that lane, in words and in the window's unit.
+fn lane_positions(
+    v: &[Word],
+    window: Window,
+    lane: impl Fn(&Word) -> Option<String>,
+) -> (Vec<usize>, Vec<u32>, Vec<usize>) {
+    let global_positions = window_positions(v, window);
+
+    let mut lanes: HashMap<Option<String>, usize> = HashMap::new();
+    let mut counts: Vec<(u32, usize)> = vec![];
+    let mut lane_ids: Vec<usize> = Vec::with_capacity(v.len());
+    let mut lane_words: Vec<u32> = Vec::with_capacity(v.len());
+    let mut positions: Vec<usize> = Vec::with_capacity(v.len());
+    for (i, word) in v.iter().enumerate() {
+        let next_id = lanes.len();
+        let id = *lanes.entry(lane(word)).or_insert(next_id);
+        if id == counts.len() {
+            counts.push((0, 0));
+        }
+        let (words, chars) = counts[id];
+        lane_ids.push(id);
+        lane_words.push(words);
+        positions.push(match window {
+            Window::Words(_) => words as usize,
+            Window::Characters(_) => chars,
+            // sentences and paragraphs are shared.
+            _ => global_positions[i],
+        });
+        counts[id] = (words + 1, chars + word.original_word.chars().count());
+    }
+
+    (lane_ids, lane_words, positions)
+}
+
+fn mark_up_in_lanes(
+    mut v: Vec<Word>,
+    stop_words: &HashSet<String>,
+    window: Window,
+    lane: impl Fn(&Word) -> Option<String>,
+) -> Vec<Word> {
+    let (lane_ids, lane_words, positions) = lane_positions(&v, window, lane);
+    let mut last_seen: HashMap<(usize, String), usize> = HashMap::new();
+
+    for i in 0..v.len() {
+        if stop_words.contains(&v[i].pure_word) {
+            continue;
+        }
+
+        let key = (lane_ids[i], v[i].key().to_owned());
+
+        if let Some(&prev) = last_seen.get(&key) {
+            if positions[i] - positions[prev] <= window.length() {
+                let distance = lane_words[i] - lane_words[prev];
+
+                let later = v[i].word_position;
+                v[prev].repeated = true;
+                v[prev].partners.push(Partner {
+                    word_position: later,
+                    distance,
+                    severity: 0,
+                });
+
+                // if they're an ending word, they still get caught
+                let earlier = v[prev].word_position;
+                v[i].repeated = true;
+                v[i].partners.push(Partner {
+                    word_position: earlier,
+                    distance,
+                    severity: 0,
+                });
+            }
+        }
+
+        last_seen.insert(key, i);
+    }
+
+    v
+}
+
+/// Lists every repetition found by `mark_up`, earliest occurrence first.
+pub fn pair_up(v: &[Word]) -> Vec<Pair> {
+    v.iter()
+        .flat_map(|word| {
+            word.partners
+                .iter()
+                .filter(move |partner| partner.word_position > word.word_position)
+                .map(move |partner| Pair {
+                    first: word.clone(),
+                    second: v[partner.word_position as usize].clone(),
+                    distance: partner.distance,
+                    severity: partner.severity,
+                })
+        })
+        .collect::<Vec<Pair>>()
+}
+
+// a field quoted if it needs to be, or for tab-separated values, with no tabs or line breaks in it.
+fn delimited_field(field: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        field.replace(['\t', '\n', '\r'], " ")
+    } else if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// One row per repetition pair, of words or of phrases, in the order they appear,
+/// for sorting and filtering in a spreadsheet. A phrase's row starts at its first word.
+/// Comma-separated, or tab-separated if the delimiter is a tab. Everything counts from one.
+pub fn delimited_report(v: &[Word], delimiter: char) -> String {
+    let header = [
+        "word",
+        "first_file",
+        "first_paragraph",
+        "first_line",
+        "first_column",
+        "first_position",
+        "second_file",
+        "second_paragraph",
+        "second_line",
+        "second_column",
+        "second_position",
+        "distance",
+        "severity",
+    ];
+    // text extracted from a file has no line or column in it, so those are left empty.
+    let occurrence = |word: &Word| {
+        let (line, column) = match word.verbatim {
+            true => (
+                word.line_number().to_string(),
+                (word.column + 1).to_string(),
+            ),
+            false => (String::new(), String::new()),
+        };
+        vec![
+            word.file
+                .as_ref()
+                .map_or(String::new(), |file| file.display().to_string()),
+            (word.paragraph + 1).to_string(),
+            line,
+            column,
+            (word.word_position + 1).to_string(),
+        ]
+    };
+
+    let mut rows = vec![header.join(&delimiter.to_string())];
+    for word in v {
+        // a phrase's pairs first, as the formatted report lists them.
+        let mut repetitions: Vec<(String, &Partner)> = vec![];
+        if let Some(phrase) = word
+            .phrase
+            .as_ref()
+            .filter(|p| p.start == word.word_position)
+        {
+            let text = v[phrase.start as usize..(phrase.start + phrase.length) as usize]
+                .iter()
+                .map(|w| w.pure_word.as_str())
+                .collect::<Vec<&str>>()
+                .join(" ");
+            repetitions.extend(phrase.partners.iter().map(|p| (text.to_owned(), p)));
+        }
+        repetitions.extend(word.partners.iter().map(|p| (word.pure_word.to_owned(), p)));
+
+        for (text, partner) in repetitions
+            .into_iter()
+            .filter(|(_, partner)| partner.word_position > word.word_position)
+        {
+            let mut fields = vec![text];
+            fields.extend(occurrence(word));
+            fields.extend(occurrence(&v[partner.word_position as usize]));
+            fields.push(partner.distance.to_string());
+            fields.push(partner.severity.to_string());
+            rows.push(
+                fields
+                    .iter()
+                    .map(|field| delimited_field(field, delimiter))
+                    .collect::<Vec<String>>()
+                    .join(&delimiter.to_string()),
+            );
+        }
+    }
+    rows.join("\n")
+}
+
+/// Flags phrases of `min_length` to `max_length` words that recur within the window.
+/// Longer phrases are found first, and their words can't be claimed by shorter ones.
+/// Stop words are not skipped, since phrases like "for a moment" are made of them.
+pub fn mark_up_phrases(
+    v: Vec<Word>,
+    min_length: usize,
+    max_length: usize,
+    window: Window,
+) -> Vec<Word> {
+    mark_up_phrases_in_lanes(v, min_length, max_length, window, |_| None)
+}
+
+/// Like `mark_up_phrases`, but each character's phrases are only checked against their own,
+/// as `mark_up_per_speaker` does for words.
+pub fn mark_up_phrases_per_speaker(
+    v: Vec<Word>,
+    min_length: usize,
+    max_length: usize,
+    window: Window,
+) -> Vec<Word> {
+    mark_up_phrases_in_lanes(v, min_length, max_length, window, |word| {
+        word.speaker.clone()
+    })
+}
+
+fn mark_up_phrases_in_lanes(
+    v: Vec<Word>,
+    min_length: usize,
+    max_length: usize,
+    window: Window,
+    lane: impl Fn(&Word) -> Option<String>,
+) -> Vec<Word> {
+    let (lane_ids, lane_words, positions) = lane_positions(&v, window, lane);
+
+    // the phrase each word belongs to, and the phrase starting at each word.
+    let mut claimed: Vec<Option<usize>> = vec![None; v.len()];
+    let mut phrases: Vec<Option<Phrase>> = vec![None; v.len()];
+
+    for length in (min_length.max(2)..=max_length).rev() {
+        if length > v.len() {
+            continue;
+        }
+
+        let mut last_seen: HashMap<(usize, Vec<&str>), usize> = HashMap::new();
+
+        for i in 0..=(v.len() - length) {
+            // skip anything already inside a longer phrase, or running into another lane
+            if claimed[i..i + length].iter().any(|c| c.is_some())
+                || lane_ids[i..i + length].iter().any(|id| *id != lane_ids[i])
+            {
+                continue;
+            }
+
+            let key = (
+                lane_ids[i],
+                v[i..i + length]
+                    .iter()
+                    .map(|word| word.key())
+                    .collect::<Vec<&str>>(),
+            );
+
+            if let Some(&prev) = last_seen.get(&key) {
+                // an overlapping occurrence ("ha ha ha") isn't a repetition yet
+                if i - prev < length {
+                    continue;
+                }
+
+                // the earlier occurrence is either free, or a phrase of exactly this shape
+                let prev_is_phrase = claimed[prev] == Some(prev)
+                    && phrases[prev].as_ref().map(|p| p.length) == Some(length as u32);
+                let prev_is_free = claimed[prev..prev + length].iter().all(|c| c.is_none());
+
+                let within = positions[i] - positions[prev] <= window.length();
+
+                if within && (prev_is_phrase || prev_is_free) {
+                    let distance = lane_words[i] - lane_words[prev];
+
+                    let earlier = phrases[prev].get_or_insert_with(|| Phrase {
+                        start: prev as u32,
+                        length: length as u32,
+                        partners: vec![],
+                    });
+                    earlier.partners.push(Partner {
+                        word_position: i as u32,
+                        distance,
+                        severity: 0,
+                    });
+
+                    phrases[i] = Some(Phrase {
+                        start: i as u32,
+                        length: length as u32,
+                        partners: vec![Partner {
+                            word_position: prev as u32,
+                            distance,
+                            severity: 0,
+                        }],
+                    });
+
+                    for c in claimed[prev..prev + length].iter_mut() {
+                        *c = Some(prev);
+                    }
+                    for c in claimed[i..i + length].iter_mut() {
+                        *c = Some(i);
                     }
                 }
-                None => {
-                    // if they're an ending word, they still get caught
-                    if matches.contains(&word.word_position) {
-                        Word {
-                            repeated: true,
-                            ..word
-                        }
-                    } else {
-                        word
+            }
+
+            last_seen.insert(key, i);
+        }
+    }
+
+    v.into_iter()
+        .zip(claimed)
+        .map(|(word, claim)| Word {
+            phrase: claim.and_then(|start| phrases[start].clone()),
+            ..word
+        })
+        .collect::<Vec<Word>>()
+}
+
+/// Ranks of common English words, most frequent first.
+pub fn get_frequencies() -> HashMap<&'static str, usize> {
+    include_str!("frequencies.txt")
+        .lines()
+        .enumerate()
+        .map(|(rank, word)| (word, rank + 1))
+        .collect::<HashMap<&'static str, usize>>()
+}
+
+/// How unusual a word is, from 0 for "the" to 1 for anything off the frequency list.
+fn rarity(word: &str, frequencies: &HashMap<&'static str, usize>) -> f32 {
+    match frequencies.get(word) {
+        Some(rank) => (*rank as f32).ln() / ((frequencies.len() + 1) as f32).ln(),
+        None => 1.0,
+    }
+}
+
+/// Scores each repetition from 0 to 100: rarer words and closer repetitions score higher.
+/// A phrase is as rare as the rarest word in it. Repetitions scoring below `min_severity` are dropped.
+pub fn score(v: Vec<Word>, min_severity: u32) -> Vec<Word> {
+    let frequencies = get_frequencies();
+    let rarities = v
+        .iter()
+        .map(|word| rarity(&word.pure_word, &frequencies))
+        .collect::<Vec<f32>>();
+    let phrase_rarity = |start: u32, length: u32| {
+        rarities[start as usize..(start + length) as usize]
+            .iter()
+            .fold(0.0, |rarest: f32, r| rarest.max(*r))
+    };
+    let severity = |rarity: f32, distance: u32| {
+        let decay = 1.0 / (1.0 + distance as f32 / 10.0);
+        (100.0 * rarity * decay).round() as u32
+    };
+
+    v.into_iter()
+        .map(|word| {
+            let own_rarity = rarities[word.word_position as usize];
+
+            let partners = word
+                .partners
+                .iter()
+                .map(|partner| {
+                    // stemmed partners may differ, so take the rarer of the two.
+                    let pair_rarity = own_rarity.max(rarities[partner.word_position as usize]);
+
+                    Partner {
+                        severity: severity(pair_rarity, partner.distance),
+                        ..partner.clone()
                     }
+                })
+                .filter(|partner| partner.severity >= min_severity)
+                .collect::<Vec<Partner>>();
+
+            let phrase = word.phrase.and_then(|phrase| {
+                let own_rarity = phrase_rarity(phrase.start, phrase.length);
+                let partners = phrase
+                    .partners
+                    .iter()
+                    .map(|partner| {
+                        let pair_rarity =
+                            own_rarity.max(phrase_rarity(partner.word_position, phrase.length));
+
+                        Partner {
+                            severity: severity(pair_rarity, partner.distance),
+                            ..partner.clone()
+                        }
+                    })
+                    .filter(|partner| partner.severity >= min_severity)
+                    .collect::<Vec<Partner>>();
+
+                match partners.is_empty() {
+                    true => None,
+                    false => Some(Phrase { partners, ..phrase }),
                 }
+            });
+
+            Word {
+                repeated: !partners.is_empty(),
+                severity: partners.iter().map(|p| p.severity).max().unwrap_or(0),
+                partners,
+                phrase,
+                ..word
             }
         })
         .collect::<Vec<Word>>()
 }
 
-pub fn report(v: &Vec<Word>) -> String {
-    let f = format!(
-        "{}",
-        v.iter()
-            .filter(|word| word.repeated)
-            .map(|word| word.represent())
-            .collect::<Vec<String>>()
-            .join("\n")
-    );
+pub fn report(v: &[Word]) -> String {
+    let mut lines = v
+        .iter()
+        .flat_map(|word| {
+            let mut lines: Vec<(u32, String)> = vec![];
+
+            if let Some(phrase) = &word.phrase {
+                if phrase.start == word.word_position {
+                    let end = (phrase.start + phrase.length) as usize;
+                    let words = &v[word.word_position as usize..end];
+                    let text = words
+                        .iter()
+                        .map(|w| w.original_word.as_str())
+                        .collect::<String>();
+                    lines.push((phrase.severity(), phrase.represent(text.trim_end(), word)));
+                }
+            }
+
+            if word.repeated {
+                lines.push((word.severity, word.represent()));
+            }
 
-    f
+            lines
+        })
+        .collect::<Vec<(u32, String)>>();
+
+    // worst first, and in the order they appear when it's a tie.
+    lines.sort_by_key(|(severity, _)| std::cmp::Reverse(*severity));
+    lines
+        .into_iter()
+        .map(|(_, line)| line)
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+// a relative path as a URI reference, with forward slashes and anything unsafe percent-encoded.
+fn sarif_uri(path: &std::path::Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// where a word, or a phrase running from it to `last`, is in the source.
+// Text extracted from a file, like a docx or Markdown file, has no lines and columns of the file's,
+// so only its file and paragraph are given.
+fn sarif_location(first: &Word, last: &Word) -> serde_json::Value {
+    // text with no file, like from stdin, is "-".
+    let artifact = serde_json::json!({
+        "uri": first.file.as_ref().map_or(String::from("-"), |file| sarif_uri(file))
+    });
+    if !first.verbatim {
+        return serde_json::json!({
+            "physicalLocation": { "artifactLocation": artifact },
+            "logicalLocations": [{ "name": first.location() }]
+        });
+    }
+
+    serde_json::json!({
+        "physicalLocation": {
+            "artifactLocation": artifact,
+            "region": {
+                "startLine": first.line_number(),
+                "startColumn": first.column + 1,
+                "endLine": last.line_number(),
+                "endColumn": last.column + last.pure_word.chars().count() as u32 + 1
+            }
+        }
+    })
+}
+
+/// Turns each flagged word into a SARIF 2.1.0 result, for review tools that collect linters' findings.
+/// Repeated words and repeated phrases are two rules, and each repetition's partners are related locations.
+pub fn sarif_report(v: &[Word]) -> serde_json::Value {
+    let results = v
+        .iter()
+        .flat_map(|word| {
+            let mut results = vec![];
+
+            if let Some(phrase) = word
+                .phrase
+                .as_ref()
+                .filter(|p| p.start == word.word_position)
+            {
+                let end = (phrase.start + phrase.length) as usize;
+                let text = v[phrase.start as usize..end]
+                    .iter()
+                    .map(|w| w.original_word.as_str())
+                    .collect::<String>();
+                results.push((
+                    "repeated-phrase",
+                    text.trim_end_matches(|c: char| !c.is_alphanumeric())
+                        .to_owned(),
+                    sarif_location(word, &v[end - 1]),
+                    phrase
+                        .partners
+                        .iter()
+                        .map(|p| {
+                            let start = &v[p.word_position as usize];
+                            let last = &v[(p.word_position + phrase.length - 1) as usize];
+                            (p, start, sarif_location(start, last))
+                        })
+                        .collect::<Vec<_>>(),
+                ));
+            }
+
+            if word.repeated {
+                results.push((
+                    "repeated-word",
+                    word.pure_word.to_owned(),
+                    sarif_location(word, word),
+                    word.partners
+                        .iter()
+                        .map(|p| {
+                            let partner = &v[p.word_position as usize];
+                            (p, partner, sarif_location(partner, partner))
+                        })
+                        .collect::<Vec<_>>(),
+                ));
+            }
+
+            results
+                .into_iter()
+                .map(|(rule, text, location, partners)| {
+                    let severity = partners.iter().map(|(p, ..)| p.severity).max().unwrap_or(0);
+                    let mentions = partners
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (p, partner, _))| {
+                            format!(
+                                "[{} {} {}]({})",
+                                p.distance,
+                                if p.distance == 1 { "word" } else { "words" },
+                                if partner.word_position > word.word_position {
+                                    "later"
+                                } else {
+                                    "earlier"
+                                },
+                                i + 1
+                            )
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    let related = partners
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (_, _, mut location))| {
+                            location["id"] = serde_json::json!(i + 1);
+                            location
+                        })
+                        .collect::<Vec<serde_json::Value>>();
+
+                    serde_json::json!({
+                        "ruleId": rule,
+                        "level": if severity >= 50 { "warning" } else { "note" },
+                        "rank": severity,
+                        "message": { "text": format!("'{}' also appears {}.", text, mentions) },
+                        "locations": [location],
+                        "relatedLocations": related
+                    })
+                })
+                .collect::<Vec<serde_json::Value>>()
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    serde_json::json!({
+        "version": "2.1.0",
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "tonal-distancing",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        {
+                            "id": "repeated-word",
+                            "shortDescription": { "text": "A word is repeated close to where it last appeared." }
+                        },
+                        {
+                            "id": "repeated-phrase",
+                            "shortDescription": { "text": "A phrase is repeated close to where it last appeared." }
+                        }
+                    ]
+                }
+            },
+            // columns count characters, as the rest of the report does.
+            "columnKind": "unicodeCodePoints",
+            "results": results
+        }]
+    })
 }
 
 pub fn rebuild_run(v: Vec<Word>) -> Vec<Run> {
     let mut run_vec: Vec<Run> = vec![];
+    let mut last_phrase: Option<u32> = None;
 
     for word in v.iter() {
-        if run_vec.len() > 0 && word.repeated == run_vec.last().unwrap().repeated {
-            run_vec
-                .last_mut()
-                .unwrap()
-                .text
-                .push_str(&word.original_word)
+        // a phrase is flagged as a whole, and kept apart from its neighbours.
+        let phrase = word.phrase.as_ref();
+        let phrase_start = phrase.map(|p| p.start);
+        let repeated = word.repeated || phrase.is_some();
+
+        // so is each repeated word, so that it keeps its identity.
+        let joins = match run_vec.last() {
+            Some(last) => {
+                (!repeated && !last.repeated) || (phrase.is_some() && phrase_start == last_phrase)
+            }
+            None => false,
+        };
+
+        if joins {
+            let run = run_vec.last_mut().unwrap();
+            run.text.push_str(&word.original_word);
+            run.byte_end = word.byte_end;
         } else {
+            let (key, partners) = match phrase {
+                Some(p) => (
+                    v[p.start as usize..(p.start + p.length) as usize]
+                        .iter()
+                        .map(|w| w.key())
+                        .collect::<Vec<&str>>()
+                        .join(" "),
+                    &p.partners,
+                ),
+                None => (word.key().to_owned(), &word.partners),
+            };
+
             run_vec.push(Run {
                 text: word.original_word.to_owned(),
-                repeated: word.repeated,
+                repeated,
+                phrase: phrase.is_some(),
+                severity: phrase.map_or(word.severity, |p| p.severity()),
+                byte_start: word.byte_start,
+                byte_end: word.byte_end,
+                line: word.line,
+                column: word.column,
+                key: if repeated { Some(key) } else { None },
+                word_position: if repeated {
+                    Some(phrase_start.unwrap_or(word.word_position))
+                } else {
+                    None
+                },
+                partner_positions: partners.iter().map(|p| p.word_position).collect(),
             })
         }
+
+        last_phrase = phrase_start;
     }
 
     return run_vec;
 }
 
+// bold, and bright after the plain ones, to stand out from the text around them.
+const TERMINAL_PALETTE: [Color; 12] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+];
+
+/// Renders the runs for the terminal, each repeated word family in a colour of its own,
+/// cycling through the palette in order of appearance. Phrases are underlined as well.
+/// Whether there's colour at all is up to `colored`, and its override.
 pub fn colorize_run(v: Vec<Run>) -> String {
     let mut s = String::from("");
+    let mut family_of: HashMap<&str, usize> = HashMap::new();
 
     for r in v.iter() {
-        if r.repeated {
-            s.push_str(&r.text.red().bold().to_string());
-        } else {
-            s.push_str(&r.text.to_string());
+        let key = match &r.key {
+            Some(key) if r.repeated => key,
+            _ => {
+                s.push_str(&r.text);
+                continue;
+            }
+        };
+        let families = family_of.len();
+        let family = *family_of.entry(key).or_insert(families);
+
+        // the trailing space or line break stays uncoloured.
+        let text = r.text.trim_end();
+        let coloured = text
+            .color(TERMINAL_PALETTE[family % TERMINAL_PALETTE.len()])
+            .bold();
+        match r.phrase {
+            true => s.push_str(&coloured.underline().to_string()),
+            false => s.push_str(&coloured.to_string()),
         }
+        s.push_str(&r.text[text.len()..]);
     }
     s
 }
 
-pub fn parse_doc(path: PathBuf) -> Result<String, TonalDistanceError> {
-    let docx = DocxFile::from_file(path).unwrap();
-    let doc = docx.parse().unwrap();
-    let mut paragraphs: Vec<Cow<str>> = vec![];
-    for body_content in doc.document.body.iter() {
-        // ignore other BodyContent types, like Table.
-        if let BodyContent::Paragraph(stuff) = body_content {
-            paragraphs.push(
-                stuff
-                    .iter_text()
-                    .map(|cow| cow.as_ref().to_string())
-                    .collect(),
+const HTML_REPORT_STYLE: &str = "
+body { font-family: Georgia, serif; line-height: 1.6; max-width: 42em; margin: 2em auto; padding: 0 1em; color: #222; }
+h1, h2 { font-family: Helvetica, Arial, sans-serif; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { text-align: left; padding: 0.2em 1em 0.2em 0; border-bottom: 1px solid #ddd; }
+.text { white-space: pre-wrap; }
+.repeated, .phrase { border-radius: 3px; padding: 0 1px; }
+.phrase { text-decoration: underline; }
+.linked { outline: 2px solid #222; }
+";
+
+// each word lights up its partners, for readers that run scripts.
+const HTML_REPORT_SCRIPT: &str = "
+document.querySelectorAll('[data-partners]').forEach(function (word) {
+  var linked = [word].concat(word.dataset.partners.split(' ').map(function (id) {
+    return document.getElementById(id);
+  }).filter(Boolean));
+  word.addEventListener('mouseenter', function () {
+    linked.forEach(function (w) { w.classList.add('linked'); });
+  });
+  word.addEventListener('mouseleave', function () {
+    linked.forEach(function (w) { w.classList.remove('linked'); });
+  });
+});
+";
+
+/// How many words the summary table of an HTML report lists.
+pub const HTML_SUMMARY_LENGTH: usize = 20;
+
+// hues a golden angle apart, so neighbouring families never look alike.
+fn family_colour(family: usize) -> String {
+    format!("hsl({:.0}, 85%, 80%)", (family as f32 * 137.508) % 360.0)
+}
+
+// a repeated word or phrase in an HTML report.
+struct Marked<'a> {
+    words: std::ops::Range<usize>,
+    key: String,
+    severity: u32,
+    partners: &'a [Partner],
+}
+
+/// Renders the marked-up words as a self-contained HTML page, for reports that leave the terminal.
+/// Each repeated word family has its own colour, hovering over an occurrence outlines its partners,
+/// and a table lists the most repeated words.
+pub fn render_html(v: &[Word]) -> String {
+    // each repeated word, and each phrase as a whole.
+    let mut marked: Vec<Marked> = vec![];
+    let mut i = 0;
+    while i < v.len() {
+        let word = &v[i];
+        match &word.phrase {
+            Some(phrase) => {
+                let words = phrase.start as usize..(phrase.start + phrase.length) as usize;
+                i = words.end;
+                marked.push(Marked {
+                    key: v[words.clone()]
+                        .iter()
+                        .map(|w| w.key())
+                        .collect::<Vec<&str>>()
+                        .join(" "),
+                    severity: phrase.severity(),
+                    partners: &phrase.partners,
+                    words,
+                });
+            }
+            None => {
+                if word.repeated {
+                    marked.push(Marked {
+                        words: i..i + 1,
+                        key: word.key().to_owned(),
+                        severity: word.severity,
+                        partners: &word.partners,
+                    });
+                }
+                i += 1;
+            }
+        }
+    }
+    let original = |words: &[Word]| {
+        words
+            .iter()
+            .map(|word| word.original_word.as_str())
+            .collect::<String>()
+    };
+
+    // the mark each word is in; a word inside a phrase only has the phrase's id.
+    let mut mark_start: Vec<Option<usize>> = vec![None; v.len()];
+    for mark in marked.iter() {
+        for start in mark_start[mark.words.clone()].iter_mut() {
+            *start = Some(mark.words.start);
+        }
+    }
+
+    // word families in order of appearance: occurrences, and the worst severity.
+    let mut families: Vec<(&str, u32, u32)> = vec![];
+    let mut family_of: HashMap<&str, usize> = HashMap::new();
+    for mark in marked.iter() {
+        let key = mark.key.as_str();
+        let family = *family_of.entry(key).or_insert_with(|| {
+            families.push((key, 0, 0));
+            families.len() - 1
+        });
+        families[family].1 += 1;
+        families[family].2 = families[family].2.max(mark.severity);
+    }
+
+    let style = families
+        .iter()
+        .enumerate()
+        .map(|(family, _)| {
+            format!(
+                ".f{} {{ background: {}; }}\n",
+                family,
+                family_colour(family)
             )
+        })
+        .collect::<String>();
+
+    let mut summary = families.iter().enumerate().collect::<Vec<_>>();
+    summary.sort_by_key(|(family, (_, occurrences, severity))| {
+        (
+            std::cmp::Reverse(*occurrences),
+            std::cmp::Reverse(*severity),
+            *family,
+        )
+    });
+    let summary = if summary.is_empty() {
+        String::from("<p>No repetitions found.</p>\n")
+    } else {
+        let rows = summary
+            .iter()
+            .take(HTML_SUMMARY_LENGTH)
+            .map(|(family, (key, occurrences, severity))| {
+                format!(
+                    "<tr><td><span class=\"repeated f{}\">{}</span></td><td>{}</td><td>{}</td></tr>\n",
+                    family,
+                    html_escape::encode_text(key),
+                    occurrences,
+                    severity
+                )
+            })
+            .collect::<String>();
+        format!(
+            "<table>\n<tr><th>Word</th><th>Occurrences</th><th>Worst severity</th></tr>\n{}</table>\n",
+            rows
+        )
+    };
+
+    let mut text = String::new();
+    let mut next = 0;
+    for mark in marked.iter() {
+        text.push_str(&html_escape::encode_text(&original(
+            &v[next..mark.words.start],
+        )));
+        next = mark.words.end;
+
+        // the space after a word isn't part of it.
+        let marked_text = original(&v[mark.words.clone()]);
+        let word = marked_text.trim_end();
+        let mut partners = mark
+            .partners
+            .iter()
+            .filter_map(|partner| mark_start[partner.word_position as usize])
+            .filter(|start| *start != mark.words.start)
+            .collect::<Vec<usize>>();
+        partners.dedup();
+        let partners = partners
+            .iter()
+            .map(|start| format!("w{}", start))
+            .collect::<Vec<String>>()
+            .join(" ");
+        text.push_str(&format!(
+            "<span id=\"w{}\" class=\"{} f{}\" data-partners=\"{}\" title=\"{}\">{}</span>{}",
+            mark.words.start,
+            if v[mark.words.start].phrase.is_some() {
+                "phrase"
+            } else {
+                "repeated"
+            },
+            family_of[mark.key.as_str()],
+            partners,
+            html_escape::encode_double_quoted_attribute(&format!(
+                "{}: severity {}",
+                mark.key, mark.severity
+            )),
+            html_escape::encode_text(word),
+            html_escape::encode_text(&marked_text[word.len()..])
+        ));
+    }
+    text.push_str(&html_escape::encode_text(&original(&v[next..])));
+
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Tonal distancing report</title>\n<style>{}{}</style>\n</head>\n<body>\n<h1>Tonal distancing report</h1>\n<h2>Most repeated words</h2>\n{}<h2>Text</h2>\n<div class=\"text\">{}</div>\n<script>{}</script>\n</body>\n</html>\n",
+        HTML_REPORT_STYLE, style, summary, text, HTML_REPORT_SCRIPT
+    )
+}
+
+fn paragraph_text(paragraph: &Paragraph) -> String {
+    paragraph
+        .iter_text()
+        .map(|cow| cow.as_ref().to_string())
+        .collect()
+}
+
+const WORDML: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const MARKUP_COMPATIBILITY: &str = "http://schemas.openxmlformats.org/markup-compatibility/2006";
+
+fn is_wordml(node: &roxmltree::Node, name: &str) -> bool {
+    node.tag_name().namespace() == Some(WORDML) && node.tag_name().name() == name
+}
+
+// text boxes are stored twice, once for Word and once as a fallback for older readers.
+fn is_fallback(node: &roxmltree::Node) -> bool {
+    node.tag_name().namespace() == Some(MARKUP_COMPATIBILITY)
+        && node.tag_name().name() == "Fallback"
+}
+
+// the lines between the text and its notes are notes too, only typed.
+fn is_separator(node: &roxmltree::Node) -> bool {
+    (is_wordml(node, "footnote") || is_wordml(node, "endnote"))
+        && node.attribute((WORDML, "type")).is_some()
+}
+
+fn wordml_text(node: roxmltree::Node, text: &mut String) {
+    wordml_text_nodes(node, text, &mut vec![]);
+}
+
+// the text, and where each of its text elements starts in it.
+fn wordml_text_nodes<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    text: &mut String,
+    pieces: &mut Vec<(roxmltree::Node<'a, 'input>, usize)>,
+) {
+    for child in node.children() {
+        if is_wordml(&child, "t") {
+            pieces.push((child, text.len()));
+            text.push_str(child.text().unwrap_or(""));
+        } else if is_wordml(&child, "tab") || is_wordml(&child, "br") {
+            text.push(' ');
+        } else if !is_wordml(&child, "p") && !is_fallback(&child) {
+            // a text box's paragraphs are read on their own.
+            wordml_text_nodes(child, text, pieces);
+        }
+    }
+}
+
+/// The paragraphs of one part of a docx file, like a header or the footnotes.
+fn wordml_sections(xml: &str, region: Region) -> Result<Vec<Section>, TonalDistanceError> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| TonalDistanceError::XmlReadError { source: e })?;
+    let sections = doc
+        .descendants()
+        .filter(|node| is_wordml(node, "p"))
+        .filter(|node| {
+            !node
+                .ancestors()
+                .any(|ancestor| is_fallback(&ancestor) || is_separator(&ancestor))
+        })
+        // the docx crate has read the rest of the body already.
+        .filter(|node| {
+            region != Region::TextBox
+                || node
+                    .ancestors()
+                    .any(|ancestor| is_wordml(&ancestor, "txbxContent"))
+        })
+        .map(|node| {
+            let mut text = String::new();
+            wordml_text(node, &mut text);
+            Section {
+                text,
+                region,
+                ..Default::default()
+            }
+        })
+        .collect::<Vec<Section>>();
+    Ok(sections)
+}
+
+fn docx_part_region(name: &str) -> Option<Region> {
+    match name {
+        "word/document.xml" => Some(Region::TextBox),
+        "word/footnotes.xml" => Some(Region::Footnote),
+        "word/endnotes.xml" => Some(Region::Endnote),
+        "word/comments.xml" => Some(Region::Comment),
+        // there can be one for the first page, and for even pages.
+        _ if name.starts_with("word/header") && name.ends_with(".xml") => Some(Region::Header),
+        _ if name.starts_with("word/footer") && name.ends_with(".xml") => Some(Region::Footer),
+        _ => None,
+    }
+}
+
+fn read_part(
+    archive: &mut zip::ZipArchive<fs::File>,
+    name: &str,
+) -> Result<String, TonalDistanceError> {
+    let mut xml = String::new();
+    archive
+        .by_name(name)
+        .map_err(|e| TonalDistanceError::ZipReadError { source: e })?
+        .read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+/// Reads a docx file, each paragraph tagged with the region it's in: the body and its tables first,
+/// then text boxes, headers, footers, footnotes, endnotes and comments.
+pub fn read_doc(path: PathBuf) -> Result<Document, TonalDistanceError> {
+    let docx = DocxFile::from_file(&path).map_err(|e| TonalDistanceError::DocXReadError {
+        source: DocError(e),
+    })?;
+    let doc = docx
+        .parse()
+        .map_err(|e| TonalDistanceError::DocXReadError {
+            source: DocError(e),
+        })?;
+    let mut sections: Vec<Section> = vec![];
+    for body_content in doc.document.body.iter() {
+        match body_content {
+            BodyContent::Paragraph(paragraph) => sections.push(Section {
+                text: paragraph_text(paragraph),
+                region: Region::Body,
+                ..Default::default()
+            }),
+            BodyContent::Table(table) => {
+                for cell in table.rows.iter().flat_map(|row| row.cells.iter()) {
+                    for content in cell.content.iter() {
+                        if let TableCellContent::Paragraph(paragraph) = content {
+                            sections.push(Section {
+                                text: paragraph_text(paragraph),
+                                region: Region::Table,
+                                ..Default::default()
+                            })
+                        }
+                    }
+                }
+            }
+            // ignore other BodyContent types, like section properties.
+            _ => {}
+        }
+    }
+
+    // the docx crate only reads the body, so the rest comes straight from the archive.
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)
+        .map_err(|e| TonalDistanceError::ZipReadError { source: e })?;
+    let mut parts = archive
+        .file_names()
+        .filter_map(|name| docx_part_region(name).map(|region| (region, name.to_owned())))
+        .collect::<Vec<(Region, String)>>();
+    parts.sort_by_key(|(region, name)| (*region as u8, name.clone()));
+    for (region, name) in parts {
+        let xml = read_part(&mut archive, &name)?;
+        sections.extend(wordml_sections(&xml, region)?);
+    }
+
+    Ok(Document { sections })
+}
+
+/// The body of a docx file, one line per paragraph.
+pub fn parse_doc(path: PathBuf) -> Result<String, TonalDistanceError> {
+    Ok(read_doc(path)?.only(&[Region::Body]).text())
+}
+
+// the run properties that come after a highlight, in the order Word insists on.
+const RPR_AFTER_HIGHLIGHT: [&str; 14] = [
+    "u",
+    "effect",
+    "bdr",
+    "shd",
+    "fitText",
+    "vertAlign",
+    "rtl",
+    "cs",
+    "em",
+    "lang",
+    "eastAsianLayout",
+    "specVanish",
+    "oMath",
+    "rPrChange",
+];
+
+const COMMENTS_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.comments+xml";
+const COMMENTS_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/comments";
+
+/// A repeated word to mark in a docx paragraph, by its byte offsets in the paragraph's text.
+struct DocxAnnotation {
+    start: usize,
+    end: usize,
+    id: u32,
+    comment: String,
+}
+
+// a run's properties, with a highlight added in its place.
+fn highlighted_run_properties(xml: &str, properties: Option<roxmltree::Node>, w: &str) -> String {
+    let highlight = format!("<{0}:highlight {0}:val=\"yellow\"/>", w);
+    let children = properties
+        .map(|node| {
+            node.children()
+                .filter(|child| child.is_element() && !is_wordml(child, "highlight"))
+                .collect::<Vec<roxmltree::Node>>()
+        })
+        .unwrap_or_default();
+    let at = children
+        .iter()
+        .position(|child| RPR_AFTER_HIGHLIGHT.contains(&child.tag_name().name()))
+        .unwrap_or(children.len());
+    let raw = |nodes: &[roxmltree::Node]| {
+        nodes
+            .iter()
+            .map(|node| &xml[node.range()])
+            .collect::<String>()
+    };
+    format!(
+        "<{0}:rPr>{1}{2}{3}</{0}:rPr>",
+        w,
+        raw(&children[..at]),
+        highlight,
+        raw(&children[at..])
+    )
+}
+
+// a run split up around the words to annotate, each of its children in a run of its own.
+fn annotated_run(
+    xml: &str,
+    run: roxmltree::Node,
+    pieces: &[(roxmltree::Node, usize)],
+    annotations: &[&DocxAnnotation],
+    w: &str,
+) -> String {
+    let properties = run.children().find(|child| is_wordml(child, "rPr"));
+    let plain = properties.map_or("", |node| &xml[node.range()]);
+    let highlighted = highlighted_run_properties(xml, properties, w);
+
+    let mut out = String::new();
+    for child in run
+        .children()
+        .filter(|child| child.is_element() && !is_wordml(child, "rPr"))
+    {
+        let offset = match pieces.iter().find(|(piece, _)| *piece == child) {
+            Some((_, offset)) => *offset,
+            None => {
+                out.push_str(&format!(
+                    "<{0}:r>{1}{2}</{0}:r>",
+                    w,
+                    plain,
+                    &xml[child.range()]
+                ));
+                continue;
+            }
+        };
+
+        let text = child.text().unwrap_or("");
+        let mut cuts = vec![0, text.len()];
+        for annotation in annotations {
+            for at in [annotation.start, annotation.end] {
+                if at > offset && at < offset + text.len() {
+                    cuts.push(at - offset);
+                }
+            }
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for segment in cuts.windows(2) {
+            let (start, end) = (offset + segment[0], offset + segment[1]);
+            let annotation = annotations
+                .iter()
+                .find(|annotation| annotation.start <= start && end <= annotation.end);
+            if let Some(annotation) = annotation.filter(|annotation| annotation.start == start) {
+                out.push_str(&format!(
+                    "<{0}:commentRangeStart {0}:id=\"{1}\"/>",
+                    w, annotation.id
+                ));
+            }
+            out.push_str(&format!(
+                "<{0}:r>{1}<{0}:t xml:space=\"preserve\">{2}</{0}:t></{0}:r>",
+                w,
+                if annotation.is_some() {
+                    highlighted.as_str()
+                } else {
+                    plain
+                },
+                html_escape::encode_text(&text[segment[0]..segment[1]])
+            ));
+            if let Some(annotation) = annotation.filter(|annotation| annotation.end == end) {
+                out.push_str(&format!(
+                    "<{0}:commentRangeEnd {0}:id=\"{1}\"/><{0}:r><{0}:commentReference {0}:id=\"{1}\"/></{0}:r>",
+                    w, annotation.id
+                ));
+            }
         }
     }
+    out
+}
+
+// inserts before the closing tag of the root element.
+fn insert_before_closing_tag(xml: &str, insertion: &str) -> String {
+    let at = xml.trim_end().rfind("</").unwrap_or(xml.len());
+    format!("{}{}{}", &xml[..at], insertion, &xml[at..])
+}
+
+fn comment_xml(id: u32, text: &str, w: &str) -> String {
+    format!(
+        "<{0}:comment {0}:id=\"{1}\" {0}:author=\"Tonal Distancing\" {0}:initials=\"TD\"><{0}:p><{0}:r><{0}:annotationRef/></{0}:r><{0}:r><{0}:t xml:space=\"preserve\">{2}</{0}:t></{0}:r></{0}:p></{0}:comment>",
+        w,
+        id,
+        html_escape::encode_text(text)
+    )
+}
+
+/// Writes a copy of a docx file with each repeated word in its body, tables and text boxes highlighted,
+/// and a Word comment on it like "'frowned' also appears 12 words later". Everything else is kept as it was.
+pub fn annotate_docx(
+    path: PathBuf,
+    window: Window,
+    stop_words: HashSet<String>,
+    stemmer: Stemmer,
+    min_severity: u32,
+) -> Result<Vec<u8>, TonalDistanceError> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)
+        .map_err(|e| TonalDistanceError::ZipReadError { source: e })?;
+    let xml = read_part(&mut archive, "word/document.xml")?;
+    let doc = roxmltree::Document::parse(&xml)
+        .map_err(|e| TonalDistanceError::XmlReadError { source: e })?;
+    let w = doc
+        .root_element()
+        .lookup_prefix(WORDML)
+        .filter(|prefix| !prefix.is_empty())
+        .unwrap_or("w");
+
+    let paragraphs = doc
+        .descendants()
+        .filter(|node| is_wordml(node, "p"))
+        .filter(|node| !node.ancestors().any(|ancestor| is_fallback(&ancestor)))
+        .map(|node| {
+            let mut text = String::new();
+            let mut pieces = vec![];
+            wordml_text_nodes(node, &mut text, &mut pieces);
+            (text, pieces)
+        })
+        .collect::<Vec<(String, Vec<(roxmltree::Node, usize)>)>>();
+
+    let document = Document {
+        sections: paragraphs
+            .iter()
+            .map(|(text, _)| Section {
+                text: text.to_owned(),
+                ..Default::default()
+            })
+            .collect(),
+    };
+    let word_vec = tag_sections(split_text_into_words(document.text())?, &document);
+    let word_vec = normalize(word_vec, &*stemmer.normalizer());
+    let word_vec = score(mark_up(word_vec, &stop_words, window), min_severity);
+
+    // new comments are numbered after any the document has already.
+    let has_comments = archive.file_names().any(|name| name == "word/comments.xml");
+    let comments = match has_comments {
+        true => Some(read_part(&mut archive, "word/comments.xml")?),
+        false => None,
+    };
+    let mut id = match &comments {
+        Some(comments) => roxmltree::Document::parse(comments)
+            .map_err(|e| TonalDistanceError::XmlReadError { source: e })?
+            .descendants()
+            .filter(|node| is_wordml(node, "comment"))
+            .filter_map(|node| node.attribute((WORDML, "id"))?.parse::<u32>().ok())
+            .map(|id| id + 1)
+            .max()
+            .unwrap_or(0),
+        None => 0,
+    };
+
+    let mut paragraph_starts = vec![];
+    let mut start = 0;
+    for (text, _) in paragraphs.iter() {
+        paragraph_starts.push(start);
+        start += text.len() + 1;
+    }
+
+    let mut annotations: Vec<Vec<DocxAnnotation>> = paragraphs.iter().map(|_| vec![]).collect();
+    for word in word_vec.iter().filter(|word| word.repeated) {
+        let nearest = match word.partners.iter().min_by_key(|partner| partner.distance) {
+            Some(partner) => partner,
+            None => continue,
+        };
+        // the paragraph it starts in; its line won't do, since a paragraph's text can hold line breaks.
+        let paragraph = paragraph_starts.partition_point(|start| *start <= word.byte_start) - 1;
+        let (start, end) = (
+            word.byte_start - paragraph_starts[paragraph],
+            word.byte_end - paragraph_starts[paragraph],
+        );
+        annotations[paragraph].push(DocxAnnotation {
+            start,
+            end,
+            id,
+            comment: format!(
+                "'{}' also appears {} {} {}",
+                &paragraphs[paragraph].0[start..end],
+                nearest.distance,
+                if nearest.distance == 1 {
+                    "word"
+                } else {
+                    "words"
+                },
+                if nearest.word_position > word.word_position {
+                    "later"
+                } else {
+                    "earlier"
+                }
+            ),
+        });
+        id += 1;
+    }
+
+    let mut edits: Vec<(std::ops::Range<usize>, String)> = vec![];
+    let mut written: Vec<&DocxAnnotation> = vec![];
+    for ((_, pieces), annotations) in paragraphs.iter().zip(annotations.iter()) {
+        // the runs with a repeated word in them, leaving alone any holding a text box.
+        let runs_of = |annotation: &DocxAnnotation| {
+            pieces
+                .iter()
+                .filter(|(piece, offset)| {
+                    *offset < annotation.end
+                        && annotation.start < offset + piece.text().unwrap_or("").len()
+                })
+                .filter_map(|(piece, _)| piece.parent())
+                .collect::<Vec<roxmltree::Node>>()
+        };
+        let annotations = annotations
+            .iter()
+            .filter(|annotation| {
+                runs_of(annotation).iter().all(|run| {
+                    is_wordml(run, "r") && !run.descendants().any(|node| is_wordml(&node, "p"))
+                })
+            })
+            .collect::<Vec<&DocxAnnotation>>();
+
+        let mut runs = annotations
+            .iter()
+            .flat_map(|annotation| runs_of(annotation))
+            .collect::<Vec<roxmltree::Node>>();
+        runs.sort_by_key(|run| run.range().start);
+        runs.dedup();
+
+        for run in runs {
+            edits.push((
+                run.range(),
+                annotated_run(&xml, run, pieces, &annotations, w),
+            ));
+        }
+        written.extend(annotations);
+    }
+
+    let mut annotated = xml.clone();
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, replacement) in edits {
+        annotated.replace_range(range, &replacement);
+    }
+
+    let mut parts: Vec<(String, String)> = vec![(String::from("word/document.xml"), annotated)];
+    match comments {
+        Some(comments) => {
+            let w = roxmltree::Document::parse(&comments)
+                .map_err(|e| TonalDistanceError::XmlReadError { source: e })?
+                .root_element()
+                .lookup_prefix(WORDML)
+                .filter(|prefix| !prefix.is_empty())
+                .unwrap_or("w")
+                .to_owned();
+            let new_comments = written
+                .iter()
+                .map(|annotation| comment_xml(annotation.id, &annotation.comment, &w))
+                .collect::<String>();
+            parts.push((
+                String::from("word/comments.xml"),
+                insert_before_closing_tag(&comments, &new_comments),
+            ))
+        }
+        None => {
+            let new_comments = written
+                .iter()
+                .map(|annotation| comment_xml(annotation.id, &annotation.comment, "w"))
+                .collect::<String>();
+            parts.push((
+                String::from("word/comments.xml"),
+                format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:comments xmlns:w=\"{}\">{}</w:comments>",
+                    WORDML, new_comments
+                ),
+            ));
+            parts.push((
+                String::from("[Content_Types].xml"),
+                insert_before_closing_tag(
+                    &read_part(&mut archive, "[Content_Types].xml")?,
+                    &format!(
+                        "<Override PartName=\"/word/comments.xml\" ContentType=\"{}\"/>",
+                        COMMENTS_CONTENT_TYPE
+                    ),
+                ),
+            ));
+            parts.push((
+                String::from("word/_rels/document.xml.rels"),
+                insert_before_closing_tag(
+                    &read_part(&mut archive, "word/_rels/document.xml.rels")?,
+                    &format!(
+                        "<Relationship Id=\"rIdTonalDistancingComments\" Type=\"{}\" Target=\"comments.xml\"/>",
+                        COMMENTS_RELATIONSHIP
+                    ),
+                ),
+            ));
+        }
+    }
+
+    let zip_error = |e| TonalDistanceError::ZipWriteError { source: e };
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i).map_err(zip_error)?;
+        match parts.iter().position(|(name, _)| name == file.name()) {
+            Some(part) => {
+                let (name, content) = parts.remove(part);
+                writer.start_file(name, options).map_err(zip_error)?;
+                writer.write_all(content.as_bytes())?;
+            }
+            None => writer.raw_copy_file(file).map_err(zip_error)?,
+        }
+    }
+    for (name, content) in parts {
+        writer.start_file(name, options).map_err(zip_error)?;
+        writer.write_all(content.as_bytes())?;
+    }
+
+    Ok(writer.finish().map_err(zip_error)?.into_inner())
+}
+
+const ODF_TEXT: &str = "urn:oasis:names:tc:opendocument:xmlns:text:1.0";
+const ODF_OFFICE: &str = "urn:oasis:names:tc:opendocument:xmlns:office:1.0";
+
+fn is_odt_paragraph(node: &roxmltree::Node) -> bool {
+    node.tag_name().namespace() == Some(ODF_TEXT) && matches!(node.tag_name().name(), "p" | "h")
+}
+
+// footnotes and comments sit inside the paragraph they're attached to, but aren't part of it.
+fn is_odt_aside(node: &roxmltree::Node) -> bool {
+    (node.tag_name().namespace() == Some(ODF_TEXT) && node.tag_name().name() == "note")
+        || (node.tag_name().namespace() == Some(ODF_OFFICE)
+            && node.tag_name().name() == "annotation")
+}
+
+fn odt_text(node: roxmltree::Node, text: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            text.push_str(child.text().unwrap_or(""));
+        } else if child.tag_name().namespace() == Some(ODF_TEXT) {
+            match child.tag_name().name() {
+                // runs of spaces are stored as a count.
+                "s" => {
+                    let count = child
+                        .attribute((ODF_TEXT, "c"))
+                        .and_then(|c| c.parse::<usize>().ok())
+                        .unwrap_or(1);
+                    text.push_str(&" ".repeat(count));
+                }
+                "tab" | "line-break" => text.push(' '),
+                "p" | "h" | "note" => {}
+                _ => odt_text(child, text),
+            }
+        } else if !is_odt_aside(&child) {
+            odt_text(child, text);
+        }
+    }
+}
+
+/// Reads an OpenDocument text file, one line per paragraph or heading, like `parse_doc`.
+pub fn parse_odt(path: PathBuf) -> Result<String, TonalDistanceError> {
+    let file = fs::File::open(path)?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| TonalDistanceError::ZipReadError { source: e })?;
+    let content = read_part(&mut archive, "content.xml")?;
+
+    let doc = roxmltree::Document::parse(&content)
+        .map_err(|e| TonalDistanceError::XmlReadError { source: e })?;
+    let paragraphs = doc
+        .descendants()
+        .filter(|node| is_odt_paragraph(node) && !node.ancestors().any(|a| is_odt_aside(&a)))
+        .map(|node| {
+            let mut text = String::new();
+            odt_text(node, &mut text);
+            text
+        })
+        .collect::<Vec<String>>();
     Ok(paragraphs.join("\n"))
 }
 
-pub fn get_content_from_file(pb: PathBuf) -> Result<String, TonalDistanceError> {
-    let ext = pb.extension().expect("Please specify the file extension");
+// each of these starts a paragraph of its own.
+const HTML_BLOCKS: [&str; 33] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "td",
+    "th",
+    "tr",
+    "ul",
+];
 
-    let content = if ext == "docx" {
-        parse_doc(pb.clone())?
-    } else {
-        std::fs::read_to_string(pb)?
+// these hold nothing a reader sees,
+const HTML_SKIPPED: [&str; 5] = ["head", "script", "style", "template", "title"];
+// and these hold text that isn't markup, so it can't be scanned for tags.
+const HTML_RAW_TEXT: [&str; 2] = ["script", "style"];
+
+// where a tag ends, minding `>` inside quoted attributes.
+fn html_tag_end(tag: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, ch) in tag.char_indices() {
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == '>' => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Strips (X)HTML down to its paragraphs, with entities decoded and whitespace collapsed.
+/// A `<br>` ends a paragraph if `breaks_paragraphs`, as web serials use it,
+/// and is a space otherwise, as a line break inside a book's paragraph.
+pub fn html_paragraphs(s: &str, breaks_paragraphs: bool) -> Vec<String> {
+    let mut paragraphs: Vec<String> = vec![];
+    let mut current = String::new();
+    // the element we're dropping, until it closes.
+    let mut skipping: Option<String> = None;
+
+    let mut flush = |current: &mut String| {
+        let text = current.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !text.is_empty() {
+            paragraphs.push(html_escape::decode_html_entities(&text).into_owned());
+        }
+        current.clear();
     };
 
-    Ok(content)
+    let mut rest = s;
+    while let Some(open) = rest.find('<') {
+        if skipping.is_none() {
+            current.push_str(&rest[..open]);
+        }
+        rest = &rest[open..];
+
+        // comments, doctypes and processing instructions.
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+
+        let end = match html_tag_end(rest) {
+            Some(end) => end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|ch: char| ch.is_whitespace() || ch == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        if HTML_RAW_TEXT.contains(&name.as_str()) && !closing && !tag.ends_with('/') {
+            let close = format!("</{}", name);
+            rest = rest
+                .to_ascii_lowercase()
+                .find(&close)
+                .and_then(|start| rest[start..].find('>').map(|end| &rest[start + end + 1..]))
+                .unwrap_or("");
+        } else if let Some(skipped) = &skipping {
+            if closing && name == *skipped {
+                skipping = None;
+            }
+        } else if HTML_SKIPPED.contains(&name.as_str()) {
+            if !closing && !tag.ends_with('/') {
+                skipping = Some(name);
+            }
+        } else if HTML_BLOCKS.contains(&name.as_str()) || (name == "br" && breaks_paragraphs) {
+            flush(&mut current);
+        } else if name == "br" {
+            current.push(' ');
+        }
+    }
+    if skipping.is_none() {
+        current.push_str(rest);
+    }
+    flush(&mut current);
+
+    paragraphs
+}
+
+/// Reads HTML as its visible text, one line per paragraph, heading, list item and so on.
+pub fn parse_html(s: &str) -> String {
+    html_paragraphs(s, true).join("\n")
 }
 
-pub fn get_stop_words(pre_stop_words: Option<Source>) -> Vec<String> {
-    if let Some(existing) = pre_stop_words {
+// the first line of a title page is a key, like "Title:".
+fn is_fountain_title_key(line: &str) -> bool {
+    line.split_once(':').is_some_and(|(key, _)| {
+        !key.is_empty() && key.chars().all(|ch| ch.is_alphabetic() || ch == ' ')
+    })
+}
+
+// in capitals, ignoring extensions like "(V.O.)" or "(cont'd)".
+fn is_fountain_shouting(line: &str) -> bool {
+    let name = line.split('(').next().unwrap_or("");
+    name.chars().any(char::is_alphabetic) && !name.chars().any(char::is_lowercase)
+}
+
+/// Reads a Fountain screenplay, tagging each paragraph as a scene heading, action, character cue,
+/// dialogue, parenthetical or transition. Dialogue and parentheticals are tagged with their speaker.
+/// Title pages, sections, synopses, notes and boneyard are left out.
+pub fn read_fountain(s: &str) -> Result<Document, TonalDistanceError> {
+    let regex =
+        |pattern| Regex::new(pattern).map_err(|e| TonalDistanceError::RegexError { source: e });
+    let hidden = regex(r"(?s)/\*.*?\*/|\[\[.*?\]\]")?;
+    let scene_heading = regex(r"(?i)^(int|ext|est|int\./ext|int/ext|i/e)[. ]")?;
+    let scene_number = regex(r"\s*#[^#]*#$")?;
+
+    let text = hidden.replace_all(s, "");
+    let lines = text.lines().map(str::trim).collect::<Vec<&str>>();
+    let blank = |i: usize| lines.get(i).map_or(true, |line| line.is_empty());
+
+    let mut sections: Vec<Section> = vec![];
+    let mut speaker: Option<String> = None;
+    let mut i = 0;
+
+    if lines
+        .first()
+        .is_some_and(|line| is_fountain_title_key(line))
+    {
+        while !blank(i) {
+            i += 1;
+        }
+    }
+
+    while i < lines.len() {
+        let line = lines[i];
+        let after_blank = i == 0 || blank(i - 1);
+        i += 1;
+
+        if line.is_empty() {
+            speaker = None;
+            continue;
+        }
+
+        let (region, text) = if speaker.is_some() {
+            if line.starts_with('(') && line.ends_with(')') {
+                (Region::Parenthetical, line)
+            } else {
+                (Region::Dialogue, line)
+            }
+        } else if let Some(action) = line.strip_prefix('!') {
+            (Region::Action, action)
+        } else if line.starts_with('#') || line.starts_with('=') {
+            // sections, synopses and page breaks are notes to the writer.
+            continue;
+        } else if line.starts_with('.') && !line.starts_with("..") {
+            (Region::SceneHeading, &line[1..])
+        } else if after_blank && scene_heading.is_match(line) {
+            (Region::SceneHeading, line)
+        } else if line.starts_with('>') && line.ends_with('<') {
+            // centred text.
+            (Region::Action, line[1..line.len() - 1].trim())
+        } else if let Some(transition) = line.strip_prefix('>') {
+            (Region::Transition, transition)
+        } else if after_blank && blank(i) && is_fountain_shouting(line) && line.ends_with("TO:") {
+            (Region::Transition, line)
+        } else if line.starts_with('@') || (after_blank && !blank(i) && is_fountain_shouting(line))
+        {
+            let cue = line.trim_start_matches('@').trim_end_matches('^').trim();
+            speaker = Some(cue.split('(').next().unwrap_or(cue).trim().to_owned());
+            (Region::Character, cue)
+        } else {
+            (Region::Action, line.trim_start_matches('~'))
+        };
+
+        let text = match region {
+            Region::SceneHeading => scene_number.replace(text, "").into_owned(),
+            _ => text.to_owned(),
+        };
+        sections.push(Section {
+            text,
+            region,
+            speaker: match region {
+                Region::Dialogue | Region::Parenthetical => speaker.clone(),
+                _ => None,
+            },
+            ..Default::default()
+        });
+    }
+
+    Ok(Document { sections })
+}
+
+/// RTF destinations whose text isn't part of the document: tables of fonts, colours, styles and lists,
+/// metadata, pictures, embedded objects, field instructions and list numbers.
+const RTF_SKIPPED: [&str; 24] = [
+    "fonttbl",
+    "colortbl",
+    "stylesheet",
+    "listtable",
+    "listoverridetable",
+    "revtbl",
+    "rsidtbl",
+    "info",
+    "pict",
+    "nonshppict",
+    "object",
+    "objdata",
+    "shpinst",
+    "fldinst",
+    "datafield",
+    "listtext",
+    "pntext",
+    "pntxta",
+    "pntxtb",
+    "themedata",
+    "colorschememapping",
+    "latentstyles",
+    "datastore",
+    "xmlnstbl",
+];
+
+fn rtf_destination_region(word: &str) -> Option<Region> {
+    match word {
+        "header" | "headerl" | "headerr" | "headerf" => Some(Region::Header),
+        "footer" | "footerl" | "footerr" | "footerf" => Some(Region::Footer),
+        // endnotes are footnotes marked \ftnalt.
+        "footnote" => Some(Region::Footnote),
+        "annotation" => Some(Region::Comment),
+        _ => None,
+    }
+}
+
+// the ANSI code page a file's \'hh escapes are in.
+fn rtf_code_page(code_page: i32) -> &'static encoding_rs::Encoding {
+    let label = match code_page {
+        874 | 1250..=1258 => format!("windows-{}", code_page),
+        932 => String::from("shift_jis"),
+        936 => String::from("gbk"),
+        949 => String::from("euc-kr"),
+        950 => String::from("big5"),
+        10000 => String::from("macintosh"),
+        65001 => String::from("utf-8"),
+        _ => String::from("windows-1252"),
+    };
+    encoding_rs::Encoding::for_label(label.as_bytes()).unwrap_or(encoding_rs::WINDOWS_1252)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RtfGroup {
+    skip: bool,
+    region: Region,
+    // how many characters stand in for each \u, for readers that don't know it.
+    fallback_length: usize,
+}
+
+struct RtfReader {
+    sections: Vec<Section>,
+    paragraphs: HashMap<Region, String>,
+    bytes: Vec<u8>,
+    encoding: &'static encoding_rs::Encoding,
+    group: RtfGroup,
+    in_table: bool,
+    high_surrogate: Option<u16>,
+}
+
+impl RtfReader {
+    // escaped bytes are decoded together, as a character can take two of them.
+    fn flush_bytes(&mut self) {
+        if !self.bytes.is_empty() {
+            let text = self
+                .encoding
+                .decode_without_bom_handling(&self.bytes)
+                .0
+                .into_owned();
+            self.bytes.clear();
+            self.push_str(&text);
+        }
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        if !self.group.skip {
+            self.bytes.push(byte);
+        }
+    }
+
+    fn push_str(&mut self, text: &str) {
+        if !self.group.skip {
+            self.flush_bytes();
+            self.paragraphs
+                .entry(self.group.region)
+                .or_default()
+                .push_str(text);
+        }
+    }
+
+    fn push_unicode(&mut self, unit: u16) {
+        let high_surrogate = self.high_surrogate.take();
+        if (0xD800..0xDC00).contains(&unit) {
+            self.high_surrogate = Some(unit);
+            return;
+        }
+        let units = match high_surrogate {
+            Some(high) => vec![high, unit],
+            None => vec![unit],
+        };
+        let text = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>();
+        self.push_str(&text);
+    }
+
+    fn end_paragraph(&mut self, region: Region) {
+        self.flush_bytes();
+        let text = self.paragraphs.remove(&region).unwrap_or_default();
+        let text = text.trim();
+        if !text.is_empty() {
+            self.sections.push(Section {
+                text: text.to_owned(),
+                region: match region {
+                    Region::Body if self.in_table => Region::Table,
+                    _ => region,
+                },
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Reads an RTF file, each paragraph tagged with the region it's in, like `read_doc`.
+/// Fonts, styles, pictures and other destinations that aren't text are skipped.
+pub fn read_rtf(rtf: &[u8]) -> Result<Document, TonalDistanceError> {
+    if !rtf.starts_with(b"{\\rtf") {
+        return Err(TonalDistanceError::MalformedFile {
+            format: "rtf",
+            reason: "it doesn't start with {\\rtf",
+        });
+    }
+
+    let mut reader = RtfReader {
+        sections: vec![],
+        paragraphs: HashMap::new(),
+        bytes: vec![],
+        encoding: encoding_rs::WINDOWS_1252,
+        group: RtfGroup {
+            skip: false,
+            region: Region::Body,
+            fallback_length: 1,
+        },
+        in_table: false,
+        high_surrogate: None,
+    };
+    let mut groups: Vec<RtfGroup> = vec![];
+    // a group that opens with a control word is a destination, and \* marks one we may ignore.
+    let mut group_start = false;
+    let mut ignorable = false;
+    let mut fallback = 0;
+    let mut i = 0;
+
+    while i < rtf.len() {
+        let byte = rtf[i];
+        i += 1;
+        match byte {
+            b'{' => {
+                reader.flush_bytes();
+                groups.push(reader.group);
+                group_start = true;
+                ignorable = false;
+                fallback = 0;
+            }
+            b'}' => {
+                reader.flush_bytes();
+                let closed = reader.group;
+                reader.group = groups.pop().unwrap_or(closed);
+                if closed.region != reader.group.region {
+                    reader.end_paragraph(closed.region);
+                }
+                group_start = false;
+                fallback = 0;
+            }
+            b'\r' | b'\n' => {}
+            b'\\' if i < rtf.len() && rtf[i].is_ascii_alphabetic() => {
+                let start = i;
+                while i < rtf.len() && rtf[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let word = String::from_utf8_lossy(&rtf[start..i]).into_owned();
+                let start = i;
+                if i < rtf.len() && rtf[i] == b'-' {
+                    i += 1;
+                }
+                while i < rtf.len() && rtf[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let parameter = std::str::from_utf8(&rtf[start..i])
+                    .ok()
+                    .and_then(|digits| digits.parse::<i32>().ok());
+                // a space only ends the control word.
+                if i < rtf.len() && rtf[i] == b' ' {
+                    i += 1;
+                }
+
+                if group_start {
+                    if let Some(region) = rtf_destination_region(&word) {
+                        reader.flush_bytes();
+                        reader.group.region = region;
+                    } else if ignorable || RTF_SKIPPED.contains(&word.as_str()) {
+                        reader.group.skip = true;
+                    }
+                }
+                group_start = false;
+                ignorable = false;
+                if word != "u" {
+                    fallback = 0;
+                }
+
+                match word.as_str() {
+                    "par" | "sect" | "cell" | "nestcell" => {
+                        reader.end_paragraph(reader.group.region)
+                    }
+                    // a soft line break, still inside the paragraph.
+                    "line" => reader.push_str(" "),
+                    "pard" => reader.in_table = false,
+                    "intbl" => reader.in_table = true,
+                    "ftnalt" if reader.group.region == Region::Footnote => {
+                        reader.group.region = Region::Endnote
+                    }
+                    "ansicpg" => reader.encoding = rtf_code_page(parameter.unwrap_or(1252)),
+                    "mac" => reader.encoding = encoding_rs::MACINTOSH,
+                    "uc" => reader.group.fallback_length = parameter.unwrap_or(1).max(0) as usize,
+                    "u" => {
+                        // parameters are signed 16-bit numbers, so higher code points are negative.
+                        reader.push_unicode(parameter.unwrap_or(0) as u16);
+                        fallback = reader.group.fallback_length;
+                    }
+                    "bin" => i += parameter.unwrap_or(0).max(0) as usize,
+                    "tab" => reader.push_str("\t"),
+                    "emdash" => reader.push_str("\u{2014}"),
+                    "endash" => reader.push_str("\u{2013}"),
+                    "lquote" => reader.push_str("\u{2018}"),
+                    "rquote" => reader.push_str("\u{2019}"),
+                    "ldblquote" => reader.push_str("\u{201c}"),
+                    "rdblquote" => reader.push_str("\u{201d}"),
+                    "bullet" => reader.push_str("\u{2022}"),
+                    "emspace" | "enspace" | "qmspace" => reader.push_str(" "),
+                    _ => {}
+                }
+            }
+            b'\\' if i < rtf.len() => {
+                let symbol = rtf[i];
+                i += 1;
+                match symbol {
+                    b'\'' => {
+                        let hex = rtf.get(i..i + 2).unwrap_or_default();
+                        i += hex.len();
+                        let escaped = std::str::from_utf8(hex)
+                            .ok()
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                        if fallback > 0 {
+                            fallback -= 1;
+                        } else if let Some(escaped) = escaped {
+                            reader.push_byte(escaped);
+                        }
+                    }
+                    b'*' => ignorable = true,
+                    b'~' => reader.push_str("\u{a0}"),
+                    b'_' => reader.push_str("-"),
+                    b'\r' | b'\n' => reader.end_paragraph(reader.group.region),
+                    b'\\' | b'{' | b'}' => reader.push_str(&(symbol as char).to_string()),
+                    // optional hyphens, and anything we don't know.
+                    _ => {}
+                }
+                group_start = group_start && symbol == b'*';
+            }
+            _ => {
+                group_start = false;
+                if fallback > 0 {
+                    fallback -= 1;
+                } else if byte.is_ascii() {
+                    reader.push_str(&(byte as char).to_string());
+                } else {
+                    reader.push_byte(byte);
+                }
+            }
+        }
+    }
+
+    // whatever's left over, like a last paragraph with no \par.
+    let mut regions = reader.paragraphs.keys().copied().collect::<Vec<Region>>();
+    regions.sort_by_key(|region| *region != Region::Body);
+    for region in regions {
+        reader.end_paragraph(region);
+    }
+
+    Ok(Document {
+        sections: reader.sections,
+    })
+}
+
+const OPF: &str = "http://www.idpf.org/2007/opf";
+
+// hrefs in the package file are relative to it.
+fn epub_path(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or("");
+    let mut path: Vec<&str> = base.split('/').collect();
+    path.pop();
+    for segment in href.split('/') {
+        match segment {
+            ".." => {
+                path.pop();
+            }
+            "." | "" => {}
+            segment => path.push(segment),
+        }
+    }
+    path.join("/")
+}
+
+/// Reads an EPUB book in spine order, a chapter per content document that has any text.
+/// Chapters are counted as they're found, so a book's own numbering may differ if it has a preface.
+pub fn read_epub(path: PathBuf) -> Result<Document, TonalDistanceError> {
+    let malformed = |reason| TonalDistanceError::MalformedFile {
+        format: "epub",
+        reason,
+    };
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)
+        .map_err(|e| TonalDistanceError::ZipReadError { source: e })?;
+
+    // the container points to the package file, which lists the content.
+    let container = read_part(&mut archive, "META-INF/container.xml")?;
+    let container = roxmltree::Document::parse(&container)
+        .map_err(|e| TonalDistanceError::XmlReadError { source: e })?;
+    let package_path = container
+        .descendants()
+        .find(|node| node.tag_name().name() == "rootfile")
+        .and_then(|node| node.attribute("full-path"))
+        .ok_or_else(|| malformed("no package file in the container"))?
+        .to_owned();
+
+    let package = read_part(&mut archive, &package_path)?;
+    let package = roxmltree::Document::parse(&package)
+        .map_err(|e| TonalDistanceError::XmlReadError { source: e })?;
+    let manifest = package
+        .descendants()
+        .filter(|node| node.has_tag_name((OPF, "item")))
+        .filter_map(|node| Some((node.attribute("id")?, node.attribute("href")?)))
+        .collect::<HashMap<&str, &str>>();
+    let spine = package
+        .descendants()
+        .filter(|node| node.has_tag_name((OPF, "itemref")))
+        // covers and the like can be left out of the reading order.
+        .filter(|node| node.attribute("linear") != Some("no"))
+        .map(|node| {
+            node.attribute("idref")
+                .and_then(|id| manifest.get(id))
+                .map(|href| epub_path(&package_path, href))
+                .ok_or_else(|| malformed("a spine item is missing from the manifest"))
+        })
+        .collect::<Result<Vec<String>, TonalDistanceError>>()?;
+
+    let mut sections: Vec<Section> = vec![];
+    let mut chapter: u32 = 0;
+    for item in spine {
+        let paragraphs = html_paragraphs(&read_part(&mut archive, &item)?, false);
+        if paragraphs.is_empty() {
+            continue;
+        }
+        sections.extend(paragraphs.into_iter().map(|text| Section {
+            text,
+            chapter: Some(chapter),
+            ..Default::default()
+        }));
+        chapter += 1;
+    }
+
+    Ok(Document { sections })
+}
+
+/// Pulls the prose out of Markdown, one line per paragraph, heading or list item,
+/// leaving out code, HTML, images, link targets and front matter.
+pub fn parse_markdown(s: &str) -> String {
+    let parser = Parser::new_ext(s, Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+    let mut paragraphs: Vec<String> = vec![];
+    let mut current = String::new();
+    // how deep we are in something that isn't prose.
+    let mut skipping: u32 = 0;
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(_))
+            | Event::Start(Tag::MetadataBlock(_))
+            | Event::Start(Tag::Image { .. }) => skipping += 1,
+            Event::End(TagEnd::CodeBlock)
+            | Event::End(TagEnd::MetadataBlock(_))
+            | Event::End(TagEnd::Image) => skipping -= 1,
+            // a list item can hold a nested list, so its text ends where either begins.
+            Event::Start(Tag::Paragraph)
+            | Event::Start(Tag::Heading { .. })
+            | Event::Start(Tag::Item)
+            | Event::End(TagEnd::Paragraph)
+            | Event::End(TagEnd::Heading(_))
+            | Event::End(TagEnd::Item) => {
+                let paragraph = std::mem::take(&mut current);
+                if !paragraph.trim().is_empty() {
+                    paragraphs.push(paragraph.trim().to_owned());
+                }
+            }
+            Event::Text(text) if skipping == 0 => current.push_str(&text),
+            Event::SoftBreak | Event::HardBreak if skipping == 0 => current.push(' '),
+            // code spans, HTML and comments.
+            _ => {}
+        }
+    }
+    if !current.trim().is_empty() {
+        paragraphs.push(current.trim().to_owned());
+    }
+
+    paragraphs.join("\n")
+}
+
+// a byte order mark settles it; failing that, UTF-16 without one shows as a NUL in every other byte.
+fn detect_encoding(bytes: &[u8]) -> Option<(&'static encoding_rs::Encoding, usize)> {
+    if let Some(found) = encoding_rs::Encoding::for_bom(bytes) {
+        return Some(found);
+    }
+
+    let sample = &bytes[..bytes.len().min(1024)];
+    let pairs = sample.len() / 2;
+    let nuls_at = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .take(pairs)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    // prose is mostly ASCII, so most high bytes are NUL, and only the odd character,
+    // like "Ā" (U+0100) or "Ѐ" (U+0400), has a NUL low byte.
+    let (even, odd) = (nuls_at(0), nuls_at(1));
+    if pairs > 0 && odd * 2 > pairs && even * 10 < pairs {
+        return Some((encoding_rs::UTF_16LE, 0));
+    }
+    if pairs > 0 && even * 2 > pairs && odd * 10 < pairs {
+        return Some((encoding_rs::UTF_16BE, 0));
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return Some((encoding_rs::UTF_8, 0));
+    }
+    // legacy text never has a NUL in it; anything that does isn't text.
+    if bytes.contains(&0) {
+        return None;
+    }
+    Some((encoding_rs::WINDOWS_1252, 0))
+}
+
+/// Decodes text to UTF-8, dropping any byte order mark.
+/// Returns `None` if the encoding is to be detected and the bytes don't look like text.
+pub fn decode_text(bytes: &[u8], encoding: TextEncoding) -> Option<String> {
+    let decoded = match encoding {
+        TextEncoding::Given(given) => given.decode_with_bom_removal(bytes).0,
+        TextEncoding::Detect => {
+            let (detected, bom_length) = detect_encoding(bytes)?;
+            detected.decode_without_bom_handling(&bytes[bom_length..]).0
+        }
+    };
+    Some(decoded.into_owned())
+}
+
+fn read_text(
+    pb: PathBuf,
+    ext: String,
+    encoding: TextEncoding,
+) -> Result<String, TonalDistanceError> {
+    match decode_text(&fs::read(pb)?, encoding) {
+        Some(content) => Ok(content),
+        // not text, and not a format we know.
+        None => Err(TonalDistanceError::UnsupportedFormat { extension: ext }),
+    }
+}
+
+/// Reads a file, tagging each paragraph with the region it comes from.
+/// The encoding only applies to text formats; docx, odt, epub and rtf files say their own.
+pub fn get_document_from_file(
+    pb: PathBuf,
+    encoding: TextEncoding,
+) -> Result<Document, TonalDistanceError> {
+    let ext = match pb.extension() {
+        Some(ext) => ext.to_string_lossy().to_string(),
+        None => return Err(TonalDistanceError::MissingExtension { path: pb }),
+    };
+
+    let document = match ext.as_str() {
+        "docx" => read_doc(pb)?,
+        "odt" => parse_odt(pb)?.into(),
+        "epub" => read_epub(pb)?,
+        "rtf" => read_rtf(&fs::read(pb)?)?,
+        "fountain" => read_fountain(&read_text(pb, ext, encoding)?)?,
+        "md" | "markdown" => parse_markdown(&read_text(pb, ext, encoding)?).into(),
+        "html" | "htm" | "xhtml" => parse_html(&read_text(pb, ext, encoding)?).into(),
+        // plain text is read as it is, line for line.
+        _ => return Ok(read_text(pb, ext, encoding)?.into()),
+    };
+
+    Ok(document.extracted())
+}
+
+/// Reads the regions of a file that are analysed by default.
+pub fn get_content_from_file(pb: PathBuf) -> Result<String, TonalDistanceError> {
+    Ok(get_document_from_file(pb, TextEncoding::Detect)?
+        .only(&Region::DEFAULT)
+        .text())
+}
+
+/// Reads several files as one document, in the order given, each section tagged with its file.
+pub fn get_document_from_files(
+    paths: Vec<PathBuf>,
+    encoding: TextEncoding,
+) -> Result<Document, TonalDistanceError> {
+    let mut sections: Vec<Section> = vec![];
+    for path in paths {
+        let document = get_document_from_file(path.clone(), encoding)?;
+        sections.extend(document.sections.into_iter().map(|section| Section {
+            file: Some(path.clone()),
+            ..section
+        }));
+    }
+    Ok(Document { sections })
+}
+
+/// The extensions a directory is searched for.
+pub const SUPPORTED_EXTENSIONS: [&str; 11] = [
+    "txt", "md", "markdown", "html", "htm", "xhtml", "docx", "odt", "epub", "fountain", "rtf",
+];
+
+/// Expands paths, directories and glob patterns into the files they name, in natural sort order,
+/// so "chapter2" comes before "chapter10". A directory stands for the files in it we can read.
+pub fn expand_paths(patterns: &[String]) -> Result<Vec<PathBuf>, TonalDistanceError> {
+    let mut paths: Vec<PathBuf> = vec![];
+    for pattern in patterns {
+        let matches = if pattern.contains(&['*', '?', '['][..]) {
+            glob::glob(pattern)
+                .map_err(|_| TonalDistanceError::ParseError {
+                    kind: "glob pattern",
+                    value: pattern.to_owned(),
+                })?
+                .filter_map(Result::ok)
+                .collect::<Vec<PathBuf>>()
+        } else {
+            vec![PathBuf::from(pattern)]
+        };
+
+        let mut found: Vec<PathBuf> = vec![];
+        for path in matches {
+            if path.is_dir() {
+                for entry in fs::read_dir(&path)? {
+                    let entry = entry?.path();
+                    let supported = entry
+                        .extension()
+                        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&&*ext.to_string_lossy()));
+                    if entry.is_file() && supported {
+                        found.push(entry);
+                    }
+                }
+            } else if path.is_file() {
+                found.push(path);
+            }
+        }
+
+        if found.is_empty() {
+            return Err(TonalDistanceError::NoMatches {
+                pattern: pattern.to_owned(),
+            });
+        }
+        paths.extend(found);
+    }
+
+    paths.sort_by(|a, b| natord::compare(&a.to_string_lossy(), &b.to_string_lossy()));
+    paths.dedup();
+    Ok(paths)
+}
+
+fn read_stop_words_file(path: PathBuf) -> Result<String, TonalDistanceError> {
+    fs::read_to_string(&path).map_err(|e| TonalDistanceError::StopWordsError { path, source: e })
+}
+
+/// The built-in stop-word list, one word per line.
+pub const DEFAULT_STOP_WORDS: &str = include_str!("stop_words.txt");
+
+pub fn default_stop_words() -> HashSet<String> {
+    DEFAULT_STOP_WORDS
+        .lines()
+        .map(String::from)
+        .collect::<HashSet<String>>()
+}
+
+/// Loads a personal stop-word list, which replaces the built-in one unless `extend_default` is set.
+/// Without a personal list, the built-in one is used.
+pub fn get_stop_words(
+    pre_stop_words: Option<Source>,
+    extend_default: bool,
+) -> Result<HashSet<String>, TonalDistanceError> {
+    let mut stop_words = if let Some(existing) = pre_stop_words {
         match existing {
             // stop words are in a file
             Source::Pb(src) => {
-                let stop_words_string =
-                    fs::read_to_string(src).expect("Could not read the stop words file");
+                let stop_words_string = read_stop_words_file(src)?;
 
                 stop_words_string
-                    .split("\n")
+                    .split('\n')
                     .map(|s| s.to_owned())
-                    .collect::<Vec<String>>()
+                    .collect::<HashSet<String>>()
             }
 
             // stop words are a string
             Source::Raw(src) => src
-                .split(",")
+                .split(',')
                 .map(|s| s.to_owned())
-                .collect::<Vec<String>>(),
+                .collect::<HashSet<String>>(),
         }
     } else {
-        let pre_vec =
-            fs::read_to_string("./stop_words.txt").expect("Could not read the stop words file");
+        return Ok(default_stop_words());
+    };
 
-        pre_vec
-            .lines()
-            .map(|s| String::from(s))
-            .collect::<Vec<String>>()
+    if extend_default {
+        stop_words.extend(default_stop_words());
     }
+
+    Ok(stop_words)
 }
 
 pub fn tell_you_how_bad(
-    s: String,
-    buffer_length: usize,
-    stop_words: Vec<String>,
-    response_type: ResponseType,
+    content: impl Into<Document>,
+    stop_words: HashSet<String>,
+    settings: Settings,
 ) -> Result<Response, TonalDistanceError> {
-    let word_vec = split_text_into_words(s)?;
+    let Settings {
+        window,
+        stemmer,
+        phrases,
+        min_severity,
+        per_speaker,
+        response,
+    } = settings;
+    let document: Document = content.into();
+    let word_vec = split_text_into_words(document.text())?;
+
+    // know where each word comes from.
+    let word_vec = tag_sections(word_vec, &document);
+
+    // bring inflected forms together.
+    let word_vec = normalize(word_vec, &*stemmer.normalizer());
 
     // mark up the structs.
-    let marked_up_vec: Vec<Word> = mark_up(word_vec, stop_words, buffer_length);
+    let marked_up_vec: Vec<Word> = if per_speaker {
+        mark_up_per_speaker(word_vec, &stop_words, window)
+    } else {
+        mark_up(word_vec, &stop_words, window)
+    };
+
+    // and, if asked, the phrases.
+    let marked_up_vec = match phrases {
+        Some(max_length) if per_speaker => {
+            mark_up_phrases_per_speaker(marked_up_vec, 2, max_length, window)
+        }
+        Some(max_length) => mark_up_phrases(marked_up_vec, 2, max_length, window),
+        None => marked_up_vec,
+    };
+
+    // weigh them, and drop what doesn't matter.
+    let marked_up_vec = score(marked_up_vec, min_severity);
 
     // create report.
-    let response: Response = match response_type {
+    let response: Response = match response {
         ResponseType::Raw => Response::VecOfRuns(rebuild_run(marked_up_vec)),
         // ResponseType::Colorized => library::rebuild(marked_up_vec, true),
         ResponseType::Formatted => Response::Str(report(&marked_up_vec)),
+        ResponseType::Html => Response::Str(render_html(&marked_up_vec)),
+        ResponseType::Sarif => Response::Str(format!("{:#}", sarif_report(&marked_up_vec))),
+        ResponseType::Csv => Response::Str(delimited_report(&marked_up_vec, ',')),
+        ResponseType::Tsv => Response::Str(delimited_report(&marked_up_vec, '\t')),
+        ResponseType::Pairs => {
+            // worst first.
+            let mut pairs = pair_up(&marked_up_vec);
+            pairs.sort_by_key(|pair| std::cmp::Reverse(pair.severity));
+            Response::VecOfPairs(pairs)
+        }
+        ResponseType::Json => Response::Json(JsonReport {
+            words: marked_up_vec
+                .iter()
+                .filter(|word| word.repeated || word.phrase.is_some())
+                .cloned()
+                .collect::<Vec<Word>>(),
+            runs: rebuild_run(marked_up_vec),
+        }),
     };
 
     Ok(response)
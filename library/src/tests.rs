@@ -4,7 +4,9 @@ mod tests {
     use crate::functions::*;
     use anyhow::Result;
     use pretty_assertions;
+    use std::collections::HashSet;
     use std::path::PathBuf;
+    use unicode_width::UnicodeWidthStr;
 
     #[test]
     fn test_splitting_text_into_words() -> Result<(), TonalDistanceError> {
@@ -17,48 +19,116 @@ mod tests {
                     paragraph: 0,
                     repeated: false,
                     original_word: String::from("here\n"),
-                    word_position: 0
+                    word_position: 0,
+                    line: 0,
+                    column: 0,
+                    byte_start: 0,
+                    byte_end: 4,
+                    sentence: 0,
+                    verbatim: true,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("i'm"),
                     paragraph: 1,
                     repeated: false,
                     original_word: String::from("I'm "),
-                    word_position: 1
+                    word_position: 1,
+                    line: 1,
+                    column: 0,
+                    byte_start: 5,
+                    byte_end: 8,
+                    sentence: 1,
+                    verbatim: true,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("here"),
                     paragraph: 1,
                     repeated: false,
                     original_word: String::from("here-\n"),
-                    word_position: 2
+                    word_position: 2,
+                    line: 1,
+                    column: 4,
+                    byte_start: 9,
+                    byte_end: 13,
+                    sentence: 1,
+                    verbatim: true,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("the"),
                     paragraph: 2,
                     repeated: false,
                     original_word: String::from("the "),
-                    word_position: 3
+                    word_position: 3,
+                    line: 2,
+                    column: 0,
+                    byte_start: 15,
+                    byte_end: 18,
+                    sentence: 2,
+                    verbatim: true,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("snow"),
                     paragraph: 2,
                     repeated: false,
                     original_word: String::from("snow "),
-                    word_position: 4
+                    word_position: 4,
+                    line: 2,
+                    column: 4,
+                    byte_start: 19,
+                    byte_end: 23,
+                    sentence: 2,
+                    verbatim: true,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("falling"),
                     paragraph: 2,
                     repeated: false,
                     original_word: String::from("falling"),
-                    word_position: 5
+                    word_position: 5,
+                    line: 2,
+                    column: 9,
+                    byte_start: 24,
+                    byte_end: 31,
+                    sentence: 2,
+                    verbatim: true,
+                    ..Default::default()
                 },
             ]
         );
         Ok(())
     }
 
+    #[test]
+    fn test_offsets_count_bytes_but_columns_count_characters() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from("déjà vu\nnaïve café"))?;
+        pretty_assertions::assert_eq!((word_vec[1].byte_start, word_vec[1].byte_end), (7, 9));
+        pretty_assertions::assert_eq!((word_vec[3].line, word_vec[3].column), (1, 6));
+        pretty_assertions::assert_eq!(word_vec[3].byte_start, 17);
+        pretty_assertions::assert_eq!(word_vec[3].line_and_column(), "2:7");
+        Ok(())
+    }
+
+    #[test]
+    fn test_extracted_text_has_no_line_and_column() {
+        let word = Word {
+            line: 4,
+            column: 2,
+            ..Default::default()
+        };
+        assert_eq!(word.line_and_column(), "-");
+        let word = Word {
+            file: Some(PathBuf::from("chapter.docx")),
+            ..word
+        };
+        assert_eq!(word.line_and_column(), "chapter.docx");
+        assert!(!word.represent().contains("4:3"));
+    }
+
     #[test]
     fn test_splitting_nothin() -> Result<(), TonalDistanceError> {
         let word_vec = split_text_into_words(String::from(""))?;
@@ -73,6 +143,394 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_doc_regions() -> Result<(), TonalDistanceError> {
+        let document = read_doc(PathBuf::from("../test_files/regions.docx"))?;
+        let sections = |region: Region| {
+            document
+                .sections
+                .iter()
+                .filter(|section| section.region == region)
+                .map(|section| section.text.as_str())
+                .collect::<Vec<&str>>()
+        };
+        pretty_assertions::assert_eq!(
+            sections(Region::Body),
+            vec![
+                "The harbour was quiet. Gulls circled.",
+                "She walked down to the harbour."
+            ]
+        );
+        pretty_assertions::assert_eq!(
+            sections(Region::Table),
+            vec!["Harbour fees", "Two shillings"]
+        );
+        // the fallback copy of the text box is skipped.
+        pretty_assertions::assert_eq!(sections(Region::TextBox), vec!["Gulls over the water"]);
+        pretty_assertions::assert_eq!(sections(Region::Header), vec!["Draft"]);
+        pretty_assertions::assert_eq!(sections(Region::Footer), vec!["Page "]);
+        // and so are the separators.
+        pretty_assertions::assert_eq!(sections(Region::Footnote), vec!["Quiet, as harbours go."]);
+        pretty_assertions::assert_eq!(sections(Region::Comment), vec!["Too many gulls?"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_sections() -> Result<(), TonalDistanceError> {
+        let document = Document {
+            sections: vec![
+                Section {
+                    text: String::from("one\ntwo"),
+                    region: Region::Body,
+                    ..Default::default()
+                },
+                Section {
+                    text: String::from("three"),
+                    region: Region::Footnote,
+                    ..Default::default()
+                },
+            ],
+        };
+        let words = tag_sections(split_text_into_words(document.text())?, &document);
+        pretty_assertions::assert_eq!(
+            words
+                .iter()
+                .map(|word| word.region)
+                .collect::<Vec<Region>>(),
+            vec![Region::Body, Region::Body, Region::Footnote]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_odt() -> Result<(), TonalDistanceError> {
+        // the footnote and the comment are left out.
+        let odtstr = parse_odt(PathBuf::from("../test_files/test.odt"))?;
+        pretty_assertions::assert_eq!(odtstr, "here\nI'm here-\nthe snow falling");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_epub() -> Result<(), TonalDistanceError> {
+        let document = read_epub(PathBuf::from("../test_files/test.epub"))?;
+        pretty_assertions::assert_eq!(
+            document
+                .sections
+                .iter()
+                .map(|section| (section.text.as_str(), section.chapter))
+                .collect::<Vec<(&str, Option<u32>)>>(),
+            // in spine order, without the cover, the contents, or what's in the head.
+            vec![
+                ("One", Some(0)),
+                ("The harbour was quiet that morning.", Some(0)),
+                ("Gulls circled the harbour\u{2019}s wall.", Some(0)),
+                ("Two", Some(1)),
+                ("She didn\u{2019}t look back. The boats waited.", Some(1)),
+                ("The boats\u{a0}left at noon.", Some(1)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_html() {
+        let text = parse_html(
+            "<DIV class=\"a>b\">One <b>bold</b>\n  word<br/>Two</div><script>x = '<p>'</script><!-- three --><p>Four&nbsp;&gt; five</p>",
+        );
+        pretty_assertions::assert_eq!(text, String::from("One bold word\nTwo\nFour\u{a0}> five"));
+    }
+
+    #[test]
+    fn test_html_line_breaks() {
+        let html = "<p>Dear Ann,<br>the boats<br/>waited.</p>";
+        pretty_assertions::assert_eq!(
+            html_paragraphs(html, true),
+            vec!["Dear Ann,", "the boats", "waited."]
+        );
+        // in a book, it's still the one paragraph.
+        pretty_assertions::assert_eq!(
+            html_paragraphs(html, false),
+            vec!["Dear Ann, the boats waited."]
+        );
+    }
+
+    #[test]
+    fn test_read_rtf() -> Result<(), TonalDistanceError> {
+        let document = read_rtf(&std::fs::read("../test_files/test.rtf")?)?;
+        pretty_assertions::assert_eq!(
+            document
+                .sections
+                .iter()
+                .map(|section| (section.region, section.text.as_str()))
+                .collect::<Vec<(Region, &str)>>(),
+            // no fonts, colours, styles, metadata, field codes or pictures.
+            vec![
+                (Region::Header, "The Lighthouse"),
+                (Region::Footer, "Page 1"),
+                (Region::Body, "Chapter One"),
+                (Region::Footnote, "A lamp of the old kind."),
+                (Region::Body, "The keeper climbed the stairs to the lamp."),
+                (
+                    Region::Body,
+                    "Caf\u{e9} talk, na\u{ef}ve talk \u{2014} \u{201c}the lamp,\u{201d} he said."
+                ),
+                (Region::Comment, "The sea again?"),
+                (Region::Body, "He watched the sea. Nobody came\u{2026}"),
+                (Region::Table, "nets"),
+                (Region::Table, "ropes"),
+                (Region::Body, "Below, the sea was calm\u{1f30a}."),
+            ]
+        );
+
+        assert!(read_rtf(b"The keeper\\par").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_text() {
+        let text = "Café — “naïve”";
+        let utf_16 = |text: &str, big_endian: bool| {
+            text.encode_utf16()
+                .flat_map(|unit| {
+                    if big_endian {
+                        unit.to_be_bytes()
+                    } else {
+                        unit.to_le_bytes()
+                    }
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        let with_bom = [&b"\xEF\xBB\xBF"[..], text.as_bytes()].concat();
+        pretty_assertions::assert_eq!(
+            decode_text(&with_bom, TextEncoding::Detect),
+            Some(String::from(text))
+        );
+        let with_bom = [&b"\xFF\xFE"[..], &utf_16(text, false)].concat();
+        pretty_assertions::assert_eq!(
+            decode_text(&with_bom, TextEncoding::Detect),
+            Some(String::from(text))
+        );
+        // no byte order mark to go on.
+        pretty_assertions::assert_eq!(
+            decode_text(&utf_16(text, true), TextEncoding::Detect),
+            Some(String::from(text))
+        );
+        // nor with typographic punctuation, and a character whose low byte is NUL.
+        let typographic =
+            "\u{201c}Ādam,\u{201d} she said \u{2014} twice\u{2026} \u{2018}Ādam.\u{2019}";
+        for big_endian in [false, true].iter() {
+            pretty_assertions::assert_eq!(
+                decode_text(&utf_16(typographic, *big_endian), TextEncoding::Detect),
+                Some(String::from(typographic))
+            );
+        }
+        let windows_1252 = b"Caf\xE9 \x97 \x93na\xEFve\x94";
+        pretty_assertions::assert_eq!(
+            decode_text(windows_1252, TextEncoding::Detect),
+            Some(String::from(text))
+        );
+        pretty_assertions::assert_eq!(
+            decode_text(
+                b"\x89PNG\r\n\x1A\n\x00\x00\x00\rIHDR\xFF",
+                TextEncoding::Detect
+            ),
+            None
+        );
+
+        // told otherwise, we don't guess.
+        let given: TextEncoding = "iso-8859-5".parse().unwrap();
+        pretty_assertions::assert_eq!(
+            decode_text(b"\xBF\xE0\xD8\xD2\xD5\xE2", given),
+            Some(String::from("Привет"))
+        );
+        assert!("klingon".parse::<TextEncoding>().is_err());
+    }
+
+    #[test]
+    fn test_expand_paths() -> Result<(), TonalDistanceError> {
+        let expected = vec![
+            PathBuf::from("../test_files/chapters/chapter1.txt"),
+            PathBuf::from("../test_files/chapters/chapter2.txt"),
+            PathBuf::from("../test_files/chapters/chapter10.txt"),
+        ];
+        // natural order, and no cover image.
+        pretty_assertions::assert_eq!(
+            expand_paths(&[String::from("../test_files/chapters")])?,
+            expected
+        );
+        // a file named again by a glob is only read once.
+        pretty_assertions::assert_eq!(
+            expand_paths(&[
+                String::from("../test_files/chapters/chapter10.txt"),
+                String::from("../test_files/chapters/*.txt"),
+            ])?,
+            expected
+        );
+        assert!(expand_paths(&[String::from("../test_files/chapters/*.md")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_fountain() -> Result<(), TonalDistanceError> {
+        let document = read_fountain(&std::fs::read_to_string("../test_files/test.fountain")?)?;
+        pretty_assertions::assert_eq!(
+            document
+                .sections
+                .iter()
+                .map(|section| (
+                    section.region,
+                    section.speaker.as_deref(),
+                    section.text.as_str()
+                ))
+                .collect::<Vec<(Region, Option<&str>, &str)>>(),
+            // no title page, notes or boneyard.
+            vec![
+                (Region::SceneHeading, None, "INT. HARBOUR OFFICE - NIGHT"),
+                (
+                    Region::Action,
+                    None,
+                    "A lamp burns low. JOHN sorts the nets."
+                ),
+                (Region::Character, None, "JOHN"),
+                (
+                    Region::Dialogue,
+                    Some("JOHN"),
+                    "Honestly, the boats are late."
+                ),
+                (Region::Character, None, "MARY (O.S.)"),
+                (Region::Parenthetical, Some("MARY"), "(calling)"),
+                (
+                    Region::Dialogue,
+                    Some("MARY"),
+                    "Honestly? They're always late."
+                ),
+                (Region::Character, None, "JOHN"),
+                (Region::Dialogue, Some("JOHN"), "The tide, honestly."),
+                (Region::Transition, None, "CUT TO:"),
+                (Region::SceneHeading, None, "EXT. PIER - CONTINUOUS"),
+                (
+                    Region::Action,
+                    None,
+                    "Mary waits on the pier. The boats are late."
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mark_up_per_speaker() -> Result<(), TonalDistanceError> {
+        let document = Document {
+            sections: vec![
+                Section {
+                    text: String::from("well well"),
+                    region: Region::Dialogue,
+                    speaker: Some(String::from("JOHN")),
+                    ..Default::default()
+                },
+                Section {
+                    text: String::from("well now"),
+                    region: Region::Dialogue,
+                    speaker: Some(String::from("MARY")),
+                    ..Default::default()
+                },
+                Section {
+                    text: String::from("now then well"),
+                    region: Region::Dialogue,
+                    speaker: Some(String::from("JOHN")),
+                    ..Default::default()
+                },
+            ],
+        };
+        let words = tag_sections(split_text_into_words(document.text())?, &document);
+        let words = mark_up_per_speaker(words, &HashSet::new(), Window::Words(3));
+        pretty_assertions::assert_eq!(
+            words
+                .iter()
+                .map(|word| word.partners.clone())
+                .collect::<Vec<Vec<Partner>>>(),
+            // Mary's "well" and "now" are hers alone, and John's last "well" is three of his words on,
+            // though five words on in the text.
+            vec![
+                vec![Partner {
+                    word_position: 1,
+                    distance: 1,
+                    severity: 0,
+                }],
+                vec![
+                    Partner {
+                        word_position: 0,
+                        distance: 1,
+                        severity: 0,
+                    },
+                    Partner {
+                        word_position: 6,
+                        distance: 3,
+                        severity: 0,
+                    },
+                ],
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                vec![Partner {
+                    word_position: 1,
+                    distance: 3,
+                    severity: 0,
+                }],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_mark_up_phrases_per_speaker() -> Result<(), TonalDistanceError> {
+        let line = |text: &str, speaker: &str| Section {
+            text: String::from(text),
+            region: Region::Dialogue,
+            speaker: Some(String::from(speaker)),
+            ..Default::default()
+        };
+        let document = Document {
+            sections: vec![
+                line("wait for a moment", "JOHN"),
+                line("for a moment, yes", "MARY"),
+                line("just for a moment", "JOHN"),
+            ],
+        };
+        let words = tag_sections(split_text_into_words(document.text())?, &document);
+        let words = mark_up_phrases_per_speaker(words, 2, 3, Window::Words(50));
+
+        // John repeats himself four of his words on; Mary's "for a moment" is hers alone.
+        pretty_assertions::assert_eq!(
+            words[1].phrase,
+            Some(Phrase {
+                start: 1,
+                length: 3,
+                partners: vec![Partner {
+                    word_position: 9,
+                    distance: 4,
+                    severity: 0,
+                }],
+            })
+        );
+        pretty_assertions::assert_eq!(words[4].phrase, None);
+        pretty_assertions::assert_eq!(words[9].phrase.as_ref().unwrap().start, 9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_markdown() {
+        let text = parse_markdown(
+            "---\ntitle: Notes\n---\n## Heading\n\nSome *prose* with `code`\nover two lines.\n\n1. first\n2. second\n\n```\nfn main() {}\n```\n",
+        );
+        pretty_assertions::assert_eq!(
+            text,
+            String::from("Heading\nSome prose with  over two lines.\nfirst\nsecond")
+        );
+    }
+
     #[test]
     fn test_markup() {
         let original_vec = vec![
@@ -82,6 +540,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("here\n"),
                 word_position: 0,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("i'm"),
@@ -89,6 +548,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("I'm "),
                 word_position: 1,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("here"),
@@ -96,6 +556,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("here-\n"),
                 word_position: 2,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("the"),
@@ -103,6 +564,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("the "),
                 word_position: 3,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("snow"),
@@ -110,6 +572,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("snow "),
                 word_position: 4,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("falling"),
@@ -117,10 +580,11 @@ mod tests {
                 repeated: false,
                 original_word: String::from("falling"),
                 word_position: 5,
+                ..Default::default()
             },
         ];
 
-        let marked_up_vec = mark_up(original_vec, vec![], 10);
+        let marked_up_vec = mark_up(original_vec, &HashSet::new(), Window::Words(10));
 
         pretty_assertions::assert_eq!(
             marked_up_vec,
@@ -131,6 +595,12 @@ mod tests {
                     repeated: true,
                     original_word: String::from("here\n"),
                     word_position: 0,
+                    partners: vec![Partner {
+                        word_position: 2,
+                        distance: 2,
+                        severity: 0
+                    }],
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("i'm"),
@@ -138,6 +608,7 @@ mod tests {
                     repeated: false,
                     original_word: String::from("I'm "),
                     word_position: 1,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("here"),
@@ -145,6 +616,12 @@ mod tests {
                     repeated: true,
                     original_word: String::from("here-\n"),
                     word_position: 2,
+                    partners: vec![Partner {
+                        word_position: 0,
+                        distance: 2,
+                        severity: 0
+                    }],
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("the"),
@@ -152,6 +629,7 @@ mod tests {
                     repeated: false,
                     original_word: String::from("the "),
                     word_position: 3,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("snow"),
@@ -159,6 +637,7 @@ mod tests {
                     repeated: false,
                     original_word: String::from("snow "),
                     word_position: 4,
+                    ..Default::default()
                 },
                 Word {
                     pure_word: String::from("falling"),
@@ -166,11 +645,211 @@ mod tests {
                     repeated: false,
                     original_word: String::from("falling"),
                     word_position: 5,
+                    ..Default::default()
                 },
             ]
         )
     }
 
+    // the scan mark_up replaced, kept to check it against: each word looks ahead for its next occurrence.
+    fn quadratic_mark_up(
+        v: Vec<Word>,
+        stop_words: &HashSet<String>,
+        buffer_length: usize,
+    ) -> Vec<Word> {
+        // (later position, earlier position) for every match found so far.
+        let mut matches: Vec<(u32, u32)> = vec![];
+
+        v.clone()
+            .into_iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if stop_words.contains(&word.pure_word) {
+                    return word;
+                }
+
+                let mut partners = matches
+                    .iter()
+                    .filter(|(later, _)| *later == word.word_position)
+                    .map(|(later, earlier)| Partner {
+                        word_position: *earlier,
+                        distance: later - earlier,
+                        severity: 0,
+                    })
+                    .collect::<Vec<Partner>>();
+
+                let end = (i + buffer_length + 1).min(v.len());
+                let match_index = v[i + 1..end].iter().position(|x| x.key() == word.key());
+
+                if let Some(matching_index) = match_index {
+                    let later = (1 + i + matching_index) as u32;
+                    matches.push((later, word.word_position));
+                    partners.push(Partner {
+                        word_position: later,
+                        distance: later - word.word_position,
+                        severity: 0,
+                    });
+                }
+
+                Word {
+                    repeated: !partners.is_empty(),
+                    partners,
+                    ..word
+                }
+            })
+            .collect::<Vec<Word>>()
+    }
+
+    #[test]
+    fn test_mark_up_matches_quadratic_scan() -> Result<(), TonalDistanceError> {
+        let vocabulary = [
+            "the", "snow", "fell", "and", "she", "frowned", "at", "it", "again", "light",
+        ];
+        let stop_words: HashSet<String> = ["the", "and", "at"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+
+        // a fixed pseudo-random sequence, so any failure can be replayed.
+        let mut seed: u64 = 42;
+        let mut next = |bound: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % bound
+        };
+
+        for _ in 0..50 {
+            let length = next(120) as usize;
+            let text = (0..length)
+                .map(|_| vocabulary[next(vocabulary.len() as u64) as usize])
+                .collect::<Vec<&str>>()
+                .join(" ");
+            let buffer_length = 1 + next(30) as usize;
+
+            let word_vec = split_text_into_words(text)?;
+            pretty_assertions::assert_eq!(
+                mark_up(word_vec.clone(), &stop_words, Window::Words(buffer_length)),
+                quadratic_mark_up(word_vec, &stop_words, buffer_length)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize() -> Result<(), TonalDistanceError> {
+        let word_vec = normalize(
+            split_text_into_words(String::from("frowned frowning"))?,
+            &*Stemmer::English.normalizer(),
+        );
+        pretty_assertions::assert_eq!(word_vec[0].key(), "frown");
+        pretty_assertions::assert_eq!(word_vec[1].key(), "frown");
+        Ok(())
+    }
+
+    #[test]
+    fn test_mark_up_phrases() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from(
+            "for a moment she paused, and for a moment she did not",
+        ))?;
+        let marked_up_vec = mark_up_phrases(word_vec, 2, 6, Window::Words(50));
+
+        // the longest match wins, and "for a" is not flagged on its own.
+        let phrase = Phrase {
+            start: 0,
+            length: 4,
+            partners: vec![Partner {
+                word_position: 6,
+                distance: 6,
+                severity: 0,
+            }],
+        };
+        pretty_assertions::assert_eq!(marked_up_vec[0].phrase, Some(phrase.clone()));
+        pretty_assertions::assert_eq!(marked_up_vec[3].phrase, Some(phrase));
+        pretty_assertions::assert_eq!(marked_up_vec[4].phrase, None);
+        pretty_assertions::assert_eq!(marked_up_vec[6].phrase.as_ref().unwrap().start, 6);
+        pretty_assertions::assert_eq!(marked_up_vec[10].phrase, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pair_up() {
+        let marked_up_vec = mark_up(
+            split_text_into_words(String::from("the snow, the snow falling")).unwrap(),
+            &vec![String::from("the")].into_iter().collect(),
+            Window::Words(10),
+        );
+
+        let pairs = pair_up(&marked_up_vec);
+        pretty_assertions::assert_eq!(pairs.len(), 1);
+        pretty_assertions::assert_eq!(pairs[0].first.word_position, 1);
+        pretty_assertions::assert_eq!(pairs[0].second.word_position, 3);
+        pretty_assertions::assert_eq!(pairs[0].distance, 2);
+    }
+
+    #[test]
+    fn test_markup_respects_lookahead() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from("snow fell on snow and more snow"))?;
+        let marked_up_vec = mark_up(word_vec, &HashSet::new(), Window::Words(3));
+
+        // 0 and 3 are three apart, 3 and 6 are three apart, 0 and 6 are not linked.
+        pretty_assertions::assert_eq!(
+            marked_up_vec[3].partners,
+            vec![
+                Partner {
+                    word_position: 0,
+                    distance: 3,
+                    severity: 0
+                },
+                Partner {
+                    word_position: 6,
+                    distance: 3,
+                    severity: 0
+                }
+            ]
+        );
+        pretty_assertions::assert_eq!(marked_up_vec[0].partners.len(), 1);
+        pretty_assertions::assert_eq!(marked_up_vec[6].partners.len(), 1);
+        pretty_assertions::assert_eq!(marked_up_vec[1].repeated, false);
+        Ok(())
+    }
+
+    #[test]
+    fn test_markup_by_sentence() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from(
+            "The snow fell and fell. Then the snow stopped.",
+        ))?;
+        let marked_up_vec = mark_up(word_vec, &HashSet::new(), Window::Sentences(0));
+
+        // "fell" repeats within its sentence, "snow" and "the" only across sentences.
+        pretty_assertions::assert_eq!(marked_up_vec[2].repeated, true);
+        pretty_assertions::assert_eq!(marked_up_vec[4].repeated, true);
+        pretty_assertions::assert_eq!(marked_up_vec[1].repeated, false);
+        pretty_assertions::assert_eq!(marked_up_vec[6].repeated, false);
+        pretty_assertions::assert_eq!(marked_up_vec[7].sentence, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_score() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from(
+            "ephemeral ephemeral and the house was a house",
+        ))?;
+        let marked_up_vec = score(mark_up(word_vec, &HashSet::new(), Window::Words(50)), 0);
+
+        // a rare word close by outranks a common word further away.
+        pretty_assertions::assert_eq!(marked_up_vec[0].severity, 91);
+        pretty_assertions::assert_eq!(marked_up_vec[1].severity, 91);
+        assert!(marked_up_vec[4].severity < marked_up_vec[0].severity);
+
+        // and a minimum severity drops the weaker ones.
+        let marked_up_vec = score(marked_up_vec, 70);
+        pretty_assertions::assert_eq!(marked_up_vec[0].repeated, true);
+        pretty_assertions::assert_eq!(marked_up_vec[4].repeated, false);
+        pretty_assertions::assert_eq!(marked_up_vec[4].partners, vec![]);
+        Ok(())
+    }
+
     #[test]
     fn test_rebuild_a_run() {
         let rebuilt_run = rebuild_run(vec![
@@ -180,6 +859,12 @@ mod tests {
                 repeated: true,
                 original_word: String::from("here\n"),
                 word_position: 0,
+                partners: vec![Partner {
+                    word_position: 2,
+                    distance: 2,
+                    severity: 0,
+                }],
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("i'm"),
@@ -187,6 +872,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("I'm "),
                 word_position: 1,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("here"),
@@ -194,6 +880,7 @@ mod tests {
                 repeated: true,
                 original_word: String::from("here-\n"),
                 word_position: 2,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("the"),
@@ -201,6 +888,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("the "),
                 word_position: 3,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("snow"),
@@ -208,6 +896,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("snow "),
                 word_position: 4,
+                ..Default::default()
             },
             Word {
                 pure_word: String::from("falling"),
@@ -215,6 +904,7 @@ mod tests {
                 repeated: false,
                 original_word: String::from("falling"),
                 word_position: 5,
+                ..Default::default()
             },
         ]);
         pretty_assertions::assert_eq!(
@@ -222,27 +912,268 @@ mod tests {
             vec![
                 Run {
                     text: String::from("here\n"),
-                    repeated: true
+                    repeated: true,
+                    key: Some(String::from("here")),
+                    word_position: Some(0),
+                    partner_positions: vec![2],
+                    ..Default::default()
                 },
                 Run {
                     text: String::from("I'm "),
-                    repeated: false
+                    repeated: false,
+                    ..Default::default()
                 },
                 Run {
                     text: String::from("here-\n"),
-                    repeated: true
+                    repeated: true,
+                    key: Some(String::from("here")),
+                    word_position: Some(2),
+                    ..Default::default()
                 },
                 Run {
                     text: String::from("the snow falling"),
-                    repeated: false
+                    repeated: false,
+                    ..Default::default()
                 }
             ]
         )
     }
 
     #[test]
-    fn test_stop_words_from_file() {
-        let stop_words = get_stop_words(Some(Source::Pb(PathBuf::from("../stop_words.txt"))));
-        pretty_assertions::assert_eq!(stop_words.contains(&String::from("and")), true)
+    fn test_render_html() {
+        let word = |original: &str, position: u32, severity: u32, partners: &[u32]| Word {
+            pure_word: original
+                .trim()
+                .trim_matches(&['<', '>', ','][..])
+                .to_lowercase(),
+            original_word: String::from(original),
+            word_position: position,
+            repeated: !partners.is_empty(),
+            severity,
+            partners: partners
+                .iter()
+                .map(|partner| Partner {
+                    word_position: *partner,
+                    distance: 2,
+                    severity,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut ice = word("ice\n", 3, 40, &[]);
+        ice.repeated = true;
+        let html = render_html(&[
+            word("<Snow> ", 0, 80, &[2]),
+            word("& ", 1, 0, &[]),
+            word("snow, ", 2, 80, &[0]),
+            ice,
+        ]);
+
+        // the source is escaped, and each family has a colour of its own.
+        assert!(html.contains("<span id=\"w0\" class=\"repeated f0\" data-partners=\"w2\" title=\"snow: severity 80\">&lt;Snow&gt;</span> &amp; <span id=\"w2\" class=\"repeated f0\" data-partners=\"w0\""));
+        assert!(html.contains("<span id=\"w3\" class=\"repeated f1\" data-partners=\"\" title=\"ice: severity 40\">ice</span>\n"));
+        assert!(html.contains(".f0 { background: hsl(0, 85%, 80%); }"));
+        assert!(!html.contains("<Snow>"));
+        // most repeated first.
+        assert!(html.contains("<tr><td><span class=\"repeated f0\">snow</span></td><td>2</td><td>80</td></tr>\n<tr><td><span class=\"repeated f1\">ice</span></td><td>1</td><td>40</td></tr>"));
+    }
+
+    #[test]
+    fn test_render_html_phrases() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from("he took a breath and took a breath"))?;
+        let html = render_html(&mark_up_phrases(word_vec, 2, 3, Window::Words(50)));
+
+        // a phrase is marked as a whole.
+        assert!(html.contains("<span id=\"w1\" class=\"phrase f0\" data-partners=\"w5\" title=\"took a breath: severity 0\">took a breath</span> and <span id=\"w5\" class=\"phrase f0\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_html_partners_in_phrases() -> Result<(), TonalDistanceError> {
+        let word_vec = split_text_into_words(String::from(
+            "breath first, then took a breath and took a breath",
+        ))?;
+        let stop_words = vec!["first", "then", "a", "and"]
+            .into_iter()
+            .map(String::from)
+            .collect::<HashSet<String>>();
+        let marked_up_vec = mark_up(word_vec, &stop_words, Window::Words(50));
+        let html = render_html(&mark_up_phrases(marked_up_vec, 2, 3, Window::Words(50)));
+
+        // the first "breath" repeats one inside a phrase, so it points at the phrase.
+        assert!(html.contains("<span id=\"w0\" class=\"repeated f0\" data-partners=\"w3\""));
+        assert!(html.contains("<span id=\"w3\" class=\"phrase f1\" data-partners=\"w7\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_words_from_file() -> Result<(), TonalDistanceError> {
+        let stop_words =
+            get_stop_words(Some(Source::Pb(PathBuf::from("src/stop_words.txt"))), false)?;
+        pretty_assertions::assert_eq!(stop_words.contains(&String::from("and")), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_stop_words() -> Result<(), TonalDistanceError> {
+        // built in, so this works from any directory.
+        let stop_words = get_stop_words(None, false)?;
+        pretty_assertions::assert_eq!(stop_words.contains("and"), true);
+
+        let stop_words = get_stop_words(Some(Source::Raw(String::from("frowned"))), false)?;
+        pretty_assertions::assert_eq!(stop_words.contains("and"), false);
+
+        let stop_words = get_stop_words(Some(Source::Raw(String::from("frowned"))), true)?;
+        pretty_assertions::assert_eq!(stop_words.contains("and"), true);
+        pretty_assertions::assert_eq!(stop_words.contains("frowned"), true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_stop_words_file() {
+        let stop_words = get_stop_words(Some(Source::Pb(PathBuf::from("../nope.txt"))), false);
+        assert!(matches!(
+            stop_words,
+            Err(TonalDistanceError::StopWordsError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing_extension() {
+        let content = get_content_from_file(PathBuf::from("../README"));
+        assert!(matches!(
+            content,
+            Err(TonalDistanceError::MissingExtension { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_response_type() {
+        let response_type = "yaml".parse::<ResponseType>();
+        assert!(matches!(
+            response_type,
+            Err(TonalDistanceError::ParseError { value, .. }) if value == "yaml"
+        ));
+    }
+
+    #[test]
+    fn test_represent_pads_by_width() {
+        let word = |original: &str| Word {
+            pure_word: original.trim().to_lowercase(),
+            original_word: String::from(original),
+            severity: 60,
+            ..Default::default()
+        };
+
+        // long and wide words still leave a gap, and the columns after a wide word line up with the rest.
+        let long = word("antidisestablishmentarianism\n").represent();
+        assert!(long.starts_with("Word: antidisestablishmentarianism Paragraph: 1"));
+        let plain = word("snow ").represent();
+        let wide = word("雪雪 ").represent();
+        assert_eq!(
+            plain.find("Paragraph"),
+            Some(UnicodeWidthStr::width(
+                &wide[..wide.find("Paragraph").unwrap()]
+            ))
+        );
+
+        // and a phrase's columns line up with a word's.
+        let phrase = Phrase {
+            start: 0,
+            length: 2,
+            partners: vec![],
+        }
+        .represent("snow fell", &word("snow "));
+        assert_eq!(phrase.find("Paragraph"), plain.find("Paragraph"));
+        assert_eq!(phrase.find("Line"), plain.find("Line"));
+        assert_eq!(phrase.find("Word Position"), plain.find("Word Position"));
+    }
+
+    #[test]
+    fn test_delimited_report() {
+        let word = |pure: &str, paragraph: u32, position: u32, partner: u32| Word {
+            pure_word: String::from(pure),
+            paragraph,
+            line: paragraph,
+            word_position: position,
+            verbatim: true,
+            partners: vec![Partner {
+                word_position: partner,
+                distance: 7,
+                severity: 30,
+            }],
+            ..Default::default()
+        };
+        let mut words = (0..8)
+            .map(|position| Word {
+                word_position: position,
+                ..Default::default()
+            })
+            .collect::<Vec<Word>>();
+        words[0] = word("well,\"no\"", 0, 0, 7);
+        words[7] = word("well,\"no\"", 2, 7, 0);
+
+        // one row for the pair, from its first word.
+        assert_eq!(delimited_report(&words, ',').lines().count(), 2);
+        assert_eq!(
+            delimited_report(&words, ',').lines().nth(1),
+            Some("\"well,\"\"no\"\"\",,1,1,1,1,,3,3,1,8,7,30")
+        );
+        assert_eq!(
+            delimited_report(&words, '\t').lines().nth(1),
+            Some("well,\"no\"\t\t1\t1\t1\t1\t\t3\t3\t1\t8\t7\t30")
+        );
+    }
+
+    #[test]
+    fn test_colorize_run() {
+        let runs = vec![
+            Run {
+                text: String::from("snow "),
+                repeated: true,
+                key: Some(String::from("snow")),
+                ..Default::default()
+            },
+            Run {
+                text: String::from("and "),
+                ..Default::default()
+            },
+            Run {
+                text: String::from("ice, "),
+                repeated: true,
+                key: Some(String::from("ice")),
+                ..Default::default()
+            },
+            Run {
+                text: String::from("Snow\n"),
+                repeated: true,
+                key: Some(String::from("snow")),
+                ..Default::default()
+            },
+        ];
+
+        // each family in its own colour, the same each time it appears.
+        colored::control::set_override(true);
+        let coloured = colorize_run(runs.clone());
+        colored::control::set_override(false);
+        let plain = colorize_run(runs);
+        colored::control::unset_override();
+
+        assert_eq!(
+            coloured,
+            "\x1b[1;31msnow\x1b[0m and \x1b[1;32mice,\x1b[0m \x1b[1;31mSnow\x1b[0m\n"
+        );
+        assert_eq!(plain, "snow and ice, Snow\n");
+    }
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Always.enabled(false));
+        assert!(!ColorChoice::Never.enabled(true));
+        assert!(!ColorChoice::Auto.enabled(false));
+        assert!(matches!(
+            "sometimes".parse::<ColorChoice>(),
+            Err(TonalDistanceError::ParseError { value, .. }) if value == "sometimes"
+        ));
     }
 }
@@ -7,9 +7,18 @@ use std::path::PathBuf;
 fn raw_report_on_a_file() -> Result<(), definitions::TonalDistanceError> {
     let content = functions::get_content_from_file(PathBuf::from("../test_files/test3.txt"))?;
 
-    let stop_words = functions::get_stop_words(Some(definitions::Source::Raw(String::from(""))));
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from(""))), false)?;
 
-    let res = functions::tell_you_how_bad(content, 50, stop_words, definitions::ResponseType::Raw)?;
+    let res = functions::tell_you_how_bad(
+        content,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Raw,
+            ..Default::default()
+        },
+    )?;
 
     match res {
         definitions::Response::VecOfRuns(resp) => {
@@ -17,42 +26,172 @@ fn raw_report_on_a_file() -> Result<(), definitions::TonalDistanceError> {
                 resp,
                 vec![
                     definitions::Run {
-                        text: String::from("yes and "),
-                        repeated: true
+                        text: String::from("yes "),
+                        repeated: true,
+                        severity: 36,
+                        byte_start: 0,
+                        byte_end: 3,
+                        line: 0,
+                        column: 0,
+                        key: Some(String::from("yes")),
+                        word_position: Some(0),
+                        partner_positions: vec![15],
+                        ..Default::default()
+                    },
+                    definitions::Run {
+                        text: String::from("and "),
+                        repeated: true,
+                        severity: 6,
+                        byte_start: 4,
+                        byte_end: 7,
+                        line: 0,
+                        column: 4,
+                        key: Some(String::from("and")),
+                        word_position: Some(1),
+                        partner_positions: vec![16],
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from(
                             "drew him down to me so he could feel my breasts all perfume "
                         ),
-                        repeated: false
+                        repeated: false,
+                        byte_start: 8,
+                        byte_end: 67,
+                        line: 0,
+                        column: 8,
+                        ..Default::default()
+                    },
+                    definitions::Run {
+                        text: String::from("yes "),
+                        repeated: true,
+                        severity: 47,
+                        byte_start: 68,
+                        byte_end: 71,
+                        line: 0,
+                        column: 68,
+                        key: Some(String::from("yes")),
+                        word_position: Some(15),
+                        partner_positions: vec![0, 24],
+                        ..Default::default()
                     },
                     definitions::Run {
-                        text: String::from("yes and "),
-                        repeated: true
+                        text: String::from("and "),
+                        repeated: true,
+                        severity: 9,
+                        byte_start: 72,
+                        byte_end: 75,
+                        line: 0,
+                        column: 72,
+                        key: Some(String::from("and")),
+                        word_position: Some(16),
+                        partner_positions: vec![1, 23],
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("his heart was going like mad "),
-                        repeated: false
+                        repeated: false,
+                        byte_start: 76,
+                        byte_end: 104,
+                        line: 0,
+                        column: 76,
+                        ..Default::default()
+                    },
+                    definitions::Run {
+                        text: String::from("and "),
+                        repeated: true,
+                        severity: 9,
+                        byte_start: 105,
+                        byte_end: 108,
+                        line: 0,
+                        column: 105,
+                        key: Some(String::from("and")),
+                        word_position: Some(23),
+                        partner_positions: vec![16],
+                        ..Default::default()
                     },
                     definitions::Run {
-                        text: String::from("and yes I "),
-                        repeated: true
+                        text: String::from("yes "),
+                        repeated: true,
+                        severity: 69,
+                        byte_start: 109,
+                        byte_end: 112,
+                        line: 0,
+                        column: 109,
+                        key: Some(String::from("yes")),
+                        word_position: Some(24),
+                        partner_positions: vec![15, 27],
+                        ..Default::default()
+                    },
+                    definitions::Run {
+                        text: String::from("I "),
+                        repeated: true,
+                        severity: 32,
+                        byte_start: 113,
+                        byte_end: 114,
+                        line: 0,
+                        column: 113,
+                        key: Some(String::from("i")),
+                        word_position: Some(25),
+                        partner_positions: vec![28],
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("said "),
-                        repeated: false
+                        repeated: false,
+                        byte_start: 115,
+                        byte_end: 119,
+                        line: 0,
+                        column: 115,
+                        ..Default::default()
                     },
                     definitions::Run {
-                        text: String::from("yes I "),
-                        repeated: true
+                        text: String::from("yes "),
+                        repeated: true,
+                        severity: 69,
+                        byte_start: 120,
+                        byte_end: 123,
+                        line: 0,
+                        column: 120,
+                        key: Some(String::from("yes")),
+                        word_position: Some(27),
+                        partner_positions: vec![24, 30],
+                        ..Default::default()
+                    },
+                    definitions::Run {
+                        text: String::from("I "),
+                        repeated: true,
+                        severity: 32,
+                        byte_start: 124,
+                        byte_end: 125,
+                        line: 0,
+                        column: 124,
+                        key: Some(String::from("i")),
+                        word_position: Some(28),
+                        partner_positions: vec![25],
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("will "),
-                        repeated: false
+                        repeated: false,
+                        byte_start: 126,
+                        byte_end: 130,
+                        line: 0,
+                        column: 126,
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("Yes."),
-                        repeated: true
+                        repeated: true,
+                        severity: 69,
+                        byte_start: 131,
+                        byte_end: 134,
+                        line: 0,
+                        column: 131,
+                        key: Some(String::from("yes")),
+                        word_position: Some(30),
+                        partner_positions: vec![27],
+                        ..Default::default()
                     },
                 ]
             );
@@ -67,20 +206,24 @@ fn raw_report_on_a_file() -> Result<(), definitions::TonalDistanceError> {
 fn formatted_report_on_a_file() -> Result<(), definitions::TonalDistanceError> {
     let content = functions::get_content_from_file(PathBuf::from("../test_files/test3.txt"))?;
 
-    let stop_words = functions::get_stop_words(Some(definitions::Source::Raw(String::from(""))));
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from(""))), false)?;
 
     let res = functions::tell_you_how_bad(
         content,
-        50,
         stop_words,
-        definitions::ResponseType::Formatted,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Formatted,
+            ..Default::default()
+        },
     )?;
 
     match res {
         definitions::Response::Str(resp) => {
             pretty_assertions::assert_eq!(
                 resp,
-                "Word: yes                  Paragraph: 1                   Word Position: 1\nWord: and                  Paragraph: 1                   Word Position: 2\nWord: yes                  Paragraph: 1                   Word Position: 16\nWord: and                  Paragraph: 1                   Word Position: 17\nWord: and                  Paragraph: 1                   Word Position: 24\nWord: yes                  Paragraph: 1                   Word Position: 25\nWord: I                    Paragraph: 1                   Word Position: 26\nWord: yes                  Paragraph: 1                   Word Position: 28\nWord: I                    Paragraph: 1                   Word Position: 29\nWord: Yes.                 Paragraph: 1                   Word Position: 31");
+                "Word: yes                  Paragraph: 1                   Line: 1:110               Word Position: 25                  Severity: 69                  Repeats: 16 (9 words away), 28 (3 words away)\nWord: yes                  Paragraph: 1                   Line: 1:121               Word Position: 28                  Severity: 69                  Repeats: 25 (3 words away), 31 (3 words away)\nWord: Yes.                 Paragraph: 1                   Line: 1:132               Word Position: 31                  Severity: 69                  Repeats: 28 (3 words away)\nWord: yes                  Paragraph: 1                   Line: 1:69                Word Position: 16                  Severity: 47                  Repeats: 1 (15 words away), 25 (9 words away)\nWord: yes                  Paragraph: 1                   Line: 1:1                 Word Position: 1                   Severity: 36                  Repeats: 16 (15 words away)\nWord: I                    Paragraph: 1                   Line: 1:114               Word Position: 26                  Severity: 32                  Repeats: 29 (3 words away)\nWord: I                    Paragraph: 1                   Line: 1:125               Word Position: 29                  Severity: 32                  Repeats: 26 (3 words away)\nWord: and                  Paragraph: 1                   Line: 1:73                Word Position: 17                  Severity: 9                   Repeats: 2 (15 words away), 24 (7 words away)\nWord: and                  Paragraph: 1                   Line: 1:106               Word Position: 24                  Severity: 9                   Repeats: 17 (7 words away)\nWord: and                  Paragraph: 1                   Line: 1:5                 Word Position: 2                   Severity: 6                   Repeats: 17 (15 words away)");
         }
         _ => panic!(),
     }
@@ -92,20 +235,24 @@ fn formatted_report_on_a_file() -> Result<(), definitions::TonalDistanceError> {
 fn stop_word_test() -> Result<(), definitions::TonalDistanceError> {
     let content = functions::get_content_from_file(PathBuf::from("../test_files/test3.txt"))?;
 
-    let stop_words = functions::get_stop_words(Some(definitions::Source::Raw(String::from("and"))));
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("and"))), false)?;
 
     let res = functions::tell_you_how_bad(
         content,
-        50,
         stop_words,
-        definitions::ResponseType::Formatted,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Formatted,
+            ..Default::default()
+        },
     )?;
 
     match res {
         definitions::Response::Str(resp) => {
             pretty_assertions::assert_eq!(
                 resp,
-                "Word: yes                  Paragraph: 1                   Word Position: 1\nWord: yes                  Paragraph: 1                   Word Position: 16\nWord: yes                  Paragraph: 1                   Word Position: 25\nWord: I                    Paragraph: 1                   Word Position: 26\nWord: yes                  Paragraph: 1                   Word Position: 28\nWord: I                    Paragraph: 1                   Word Position: 29\nWord: Yes.                 Paragraph: 1                   Word Position: 31");
+                "Word: yes                  Paragraph: 1                   Line: 1:110               Word Position: 25                  Severity: 69                  Repeats: 16 (9 words away), 28 (3 words away)\nWord: yes                  Paragraph: 1                   Line: 1:121               Word Position: 28                  Severity: 69                  Repeats: 25 (3 words away), 31 (3 words away)\nWord: Yes.                 Paragraph: 1                   Line: 1:132               Word Position: 31                  Severity: 69                  Repeats: 28 (3 words away)\nWord: yes                  Paragraph: 1                   Line: 1:69                Word Position: 16                  Severity: 47                  Repeats: 1 (15 words away), 25 (9 words away)\nWord: yes                  Paragraph: 1                   Line: 1:1                 Word Position: 1                   Severity: 36                  Repeats: 16 (15 words away)\nWord: I                    Paragraph: 1                   Line: 1:114               Word Position: 26                  Severity: 32                  Repeats: 29 (3 words away)\nWord: I                    Paragraph: 1                   Line: 1:125               Word Position: 29                  Severity: 32                  Repeats: 26 (3 words away)");
         }
         _ => panic!(),
     }
@@ -117,9 +264,18 @@ fn stop_word_test() -> Result<(), definitions::TonalDistanceError> {
 fn report_on_docx() -> Result<(), definitions::TonalDistanceError> {
     let docstr = functions::parse_doc(PathBuf::from("../test_files/test.docx"))?;
 
-    let stop_words = functions::get_stop_words(Some(definitions::Source::Raw(String::from("and"))));
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("and"))), false)?;
 
-    let res = functions::tell_you_how_bad(docstr, 50, stop_words, definitions::ResponseType::Raw)?;
+    let res = functions::tell_you_how_bad(
+        docstr,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Raw,
+            ..Default::default()
+        },
+    )?;
 
     match res {
         definitions::Response::VecOfRuns(resp) => {
@@ -128,19 +284,47 @@ fn report_on_docx() -> Result<(), definitions::TonalDistanceError> {
                 vec![
                     definitions::Run {
                         text: String::from("here\n"),
-                        repeated: true
+                        repeated: true,
+                        severity: 62,
+                        byte_start: 0,
+                        byte_end: 4,
+                        line: 0,
+                        column: 0,
+                        key: Some(String::from("here")),
+                        word_position: Some(0),
+                        partner_positions: vec![2],
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("I'm "),
-                        repeated: false
+                        repeated: false,
+                        byte_start: 5,
+                        byte_end: 8,
+                        line: 1,
+                        column: 0,
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("here-\n"),
-                        repeated: true
+                        repeated: true,
+                        severity: 62,
+                        byte_start: 9,
+                        byte_end: 13,
+                        line: 1,
+                        column: 4,
+                        key: Some(String::from("here")),
+                        word_position: Some(2),
+                        partner_positions: vec![0],
+                        ..Default::default()
                     },
                     definitions::Run {
                         text: String::from("the snow falling"),
-                        repeated: false
+                        repeated: false,
+                        byte_start: 15,
+                        byte_end: 31,
+                        line: 2,
+                        column: 0,
+                        ..Default::default()
                     }
                 ]
             );
@@ -149,3 +333,695 @@ fn report_on_docx() -> Result<(), definitions::TonalDistanceError> {
     }
     Ok(())
 }
+
+#[test]
+fn stemmed_report() -> Result<(), definitions::TonalDistanceError> {
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("she"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        String::from("she frowned at him, and then she kept frowning"),
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            stemmer: definitions::Stemmer::English,
+            response: definitions::ResponseType::Pairs,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfPairs(resp) => {
+            pretty_assertions::assert_eq!(resp.len(), 1);
+            pretty_assertions::assert_eq!(resp[0].first.pure_word, "frowned");
+            pretty_assertions::assert_eq!(resp[0].second.pure_word, "frowning");
+        }
+        _ => panic!(),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn phrase_report() -> Result<(), definitions::TonalDistanceError> {
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("he"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        String::from("She took a deep breath. Then he took a deep breath again."),
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            phrases: Some(6),
+            response: definitions::ResponseType::Raw,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfRuns(resp) => {
+            pretty_assertions::assert_eq!(
+                resp,
+                vec![
+                    definitions::Run {
+                        text: String::from("She "),
+                        repeated: false,
+                        phrase: false,
+                        severity: 0,
+                        byte_start: 0,
+                        byte_end: 3,
+                        line: 0,
+                        column: 0,
+                        key: None,
+                        word_position: None,
+                        partner_positions: vec![],
+                    },
+                    definitions::Run {
+                        text: String::from("took a deep breath. "),
+                        repeated: true,
+                        phrase: true,
+                        severity: 63,
+                        byte_start: 4,
+                        byte_end: 22,
+                        line: 0,
+                        column: 4,
+                        key: Some(String::from("took a deep breath")),
+                        word_position: Some(1),
+                        partner_positions: vec![7],
+                    },
+                    definitions::Run {
+                        text: String::from("Then he "),
+                        repeated: false,
+                        phrase: false,
+                        severity: 0,
+                        byte_start: 24,
+                        byte_end: 31,
+                        line: 0,
+                        column: 24,
+                        key: None,
+                        word_position: None,
+                        partner_positions: vec![],
+                    },
+                    definitions::Run {
+                        text: String::from("took a deep breath "),
+                        repeated: true,
+                        phrase: true,
+                        severity: 63,
+                        byte_start: 32,
+                        byte_end: 50,
+                        line: 0,
+                        column: 32,
+                        key: Some(String::from("took a deep breath")),
+                        word_position: Some(7),
+                        partner_positions: vec![1],
+                    },
+                    definitions::Run {
+                        text: String::from("again."),
+                        repeated: false,
+                        phrase: false,
+                        severity: 0,
+                        byte_start: 51,
+                        byte_end: 56,
+                        line: 0,
+                        column: 51,
+                        key: None,
+                        word_position: None,
+                        partner_positions: vec![],
+                    },
+                ]
+            );
+        }
+        _ => panic!(),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn min_severity_drops_phrases() -> Result<(), definitions::TonalDistanceError> {
+    let report = |min_severity| {
+        functions::tell_you_how_bad(
+            String::from("For a moment she paused, and for a moment she did not."),
+            functions::default_stop_words(),
+            definitions::Settings {
+                window: definitions::Window::Words(50),
+                phrases: Some(4),
+                min_severity,
+                ..Default::default()
+            },
+        )
+    };
+
+    match (report(0)?, report(90)?) {
+        (definitions::Response::Str(all), definitions::Response::Str(worst)) => {
+            // a phrase is as bad as its rarest word, "moment" 6 words away.
+            assert!(all.contains("Phrase: For a moment she"));
+            assert!(!worst.contains("Phrase:"));
+        }
+        _ => panic!("Should be strings"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn json_report() -> Result<(), definitions::TonalDistanceError> {
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from(""))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        String::from("Snow on snow."),
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Json,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::Json(resp) => {
+            let value = serde_json::to_value(&resp).unwrap();
+            pretty_assertions::assert_eq!(value["words"].as_array().unwrap().len(), 2);
+            pretty_assertions::assert_eq!(value["words"][1]["pure_word"], "snow");
+            pretty_assertions::assert_eq!(value["words"][1]["original_word"], "snow.");
+            pretty_assertions::assert_eq!(value["words"][1]["paragraph"], 0);
+            pretty_assertions::assert_eq!(value["words"][1]["word_position"], 2);
+            pretty_assertions::assert_eq!(value["words"][1]["byte_start"], 8);
+            pretty_assertions::assert_eq!(value["words"][1]["byte_end"], 12);
+            pretty_assertions::assert_eq!(value["runs"][0]["text"], "Snow ");
+        }
+        _ => panic!(),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn report_on_markdown() -> Result<(), definitions::TonalDistanceError> {
+    let content = functions::get_content_from_file(PathBuf::from("../test_files/test.md"))?;
+
+    pretty_assertions::assert_eq!(
+        content,
+        String::from(
+            "The Lighthouse\nShe climbed the stairs of the lighthouse slowly. The keeper waited at the top.\nthe lamp\nthe  switch\nthe window\nDusk fell."
+        )
+    );
+
+    let words = functions::split_text_into_words(content)?;
+    let lighthouses = words
+        .iter()
+        .filter(|word| word.pure_word == "lighthouse")
+        .map(|word| word.paragraph)
+        .collect::<Vec<u32>>();
+    // the title, then the link text; the URL, comment, code and image are left out.
+    pretty_assertions::assert_eq!(lighthouses, vec![0, 1]);
+    // the nested list item is a paragraph of its own.
+    pretty_assertions::assert_eq!(words.last().map(|word| word.paragraph), Some(5));
+    Ok(())
+}
+
+#[test]
+fn report_on_docx_regions() -> Result<(), definitions::TonalDistanceError> {
+    let document = functions::get_document_from_file(
+        PathBuf::from("../test_files/regions.docx"),
+        definitions::TextEncoding::Detect,
+    )?
+    .only(&[definitions::Region::Body, definitions::Region::Footnote]);
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            stemmer: definitions::Stemmer::English,
+            response: definitions::ResponseType::Pairs,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfPairs(resp) => {
+            pretty_assertions::assert_eq!(
+                resp.iter()
+                    .map(|pair| pair.represent())
+                    .collect::<Vec<String>>(),
+                vec![
+                    String::from("Word: harbour. (Paragraph: 2, Word Position: 12)  Repeated: harbours (Paragraph: 3 (footnote), Word Position: 15)  Distance: 3  Severity: 77"),
+                    String::from("Word: harbour (Paragraph: 1, Word Position: 2)  Repeated: harbour. (Paragraph: 2, Word Position: 12)  Distance: 10  Severity: 50"),
+                    String::from("Word: quiet. (Paragraph: 1, Word Position: 4)  Repeated: Quiet, (Paragraph: 3 (footnote), Word Position: 13)  Distance: 9  Severity: 50"),
+                ]
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn report_on_epub() -> Result<(), definitions::TonalDistanceError> {
+    let document = functions::get_document_from_file(
+        PathBuf::from("../test_files/test.epub"),
+        definitions::TextEncoding::Detect,
+    )?;
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Pairs,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfPairs(resp) => {
+            pretty_assertions::assert_eq!(
+                resp.iter()
+                    .map(|pair| pair.represent())
+                    .collect::<Vec<String>>(),
+                vec![
+                    String::from("Word: boats (Chapter 2, paragraph 2, Word Position: 21)  Repeated: boats (Chapter 2, paragraph 3, Word Position: 24)  Distance: 3  Severity: 77"),
+                    String::from("Word: harbour (Chapter 1, paragraph 2, Word Position: 3)  Repeated: harbour\u{2019} (Chapter 1, paragraph 3, Word Position: 11)  Distance: 8  Severity: 56"),
+                ]
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn report_on_html() -> Result<(), definitions::TonalDistanceError> {
+    let content = functions::get_content_from_file(PathBuf::from("../test_files/test.html"))?;
+
+    // no title, styles, scripts, attributes or tag names.
+    pretty_assertions::assert_eq!(
+        content,
+        String::from(
+            "Previous chapter\nChapter Three\nThe tide came in over the flats.\nNobody watched it.\nFish & chips, \u{201c}twice\u{201d}, said the harbour master.\nnets\nropes"
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn report_on_rtf() -> Result<(), definitions::TonalDistanceError> {
+    let content = functions::get_content_from_file(PathBuf::from("../test_files/test.rtf"))?;
+
+    // no control words, and no header, footer or comment.
+    pretty_assertions::assert_eq!(
+        content,
+        String::from(
+            "Chapter One\nA lamp of the old kind.\nThe keeper climbed the stairs to the lamp.\nCaf\u{e9} talk, na\u{ef}ve talk \u{2014} \u{201c}the lamp,\u{201d} he said.\nHe watched the sea. Nobody came\u{2026}\nnets\nropes\nBelow, the sea was calm\u{1f30a}."
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn report_on_fountain_per_character() -> Result<(), definitions::TonalDistanceError> {
+    let document = functions::get_document_from_file(
+        PathBuf::from("../test_files/test.fountain"),
+        definitions::TextEncoding::Detect,
+    )?
+    .only(&definitions::Region::DEFAULT);
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            per_speaker: true,
+            response: definitions::ResponseType::Pairs,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfPairs(resp) => {
+            // John's "honestly" is his own tic; Mary's, and the action's "boats", don't count against it.
+            pretty_assertions::assert_eq!(
+                resp.iter()
+                    .map(|pair| pair.represent())
+                    .collect::<Vec<String>>(),
+                vec![String::from("Word: Honestly, (Paragraph: 2 (dialogue: JOHN), Word Position: 9)  Repeated: honestly. (Paragraph: 5 (dialogue: JOHN), Word Position: 21)  Distance: 7  Severity: 59")]
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn report_on_several_files() -> Result<(), definitions::TonalDistanceError> {
+    let paths = functions::expand_paths(&[String::from("../test_files/chapters/*")])?;
+    let document = functions::get_document_from_files(paths, definitions::TextEncoding::Detect)?;
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Pairs,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfPairs(resp) => {
+            // chapter 10 comes after chapter 2, not before it.
+            pretty_assertions::assert_eq!(
+                resp.iter()
+                    .map(|pair| pair.represent())
+                    .collect::<Vec<String>>(),
+                vec![
+                    String::from("Word: lamp. (Paragraph: 2, Line: ../test_files/chapters/chapter1.txt:2:12, Word Position: 10)  Repeated: lamp (Paragraph: 5, Line: ../test_files/chapters/chapter10.txt:2:5, Word Position: 22)  Distance: 12  Severity: 45"),
+                    String::from("Word: keeper (Paragraph: 1, Line: ../test_files/chapters/chapter1.txt:1:16, Word Position: 3)  Repeated: keeper (Paragraph: 4, Line: ../test_files/chapters/chapter10.txt:1:13, Word Position: 19)  Distance: 16  Severity: 38"),
+                ]
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn report_on_legacy_encodings() -> Result<(), definitions::TonalDistanceError> {
+    let expected = String::from("Café talk — the naïve keeper’s café.\nNobody came.");
+    for file in ["windows-1252.txt", "utf-16.txt"] {
+        let content = functions::get_content_from_file(PathBuf::from("../test_files").join(file))?;
+        pretty_assertions::assert_eq!(content, expected);
+    }
+
+    let document = functions::get_document_from_file(
+        PathBuf::from("../test_files/windows-1252.txt"),
+        "windows-1252".parse::<definitions::TextEncoding>()?,
+    )?;
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Pairs,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::VecOfPairs(resp) => {
+            pretty_assertions::assert_eq!(
+                resp.iter()
+                    .map(|pair| pair.represent())
+                    .collect::<Vec<String>>(),
+                vec![String::from("Word: Café (Paragraph: 1, Word Position: 1)  Repeated: café. (Paragraph: 1, Word Position: 7)  Distance: 6  Severity: 63")]
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn annotate_a_docx() -> Result<(), definitions::TonalDistanceError> {
+    let stop_words = functions::get_stop_words(
+        Some(definitions::Source::Raw(String::from(
+            "the,at,he,later,again",
+        ))),
+        false,
+    )?;
+
+    let annotated = functions::annotate_docx(
+        PathBuf::from("../test_files/annotate.docx"),
+        definitions::Window::Words(50),
+        stop_words,
+        definitions::Stemmer::None,
+        0,
+    )?;
+    let path = std::env::temp_dir().join("tonal-distancing-annotated.docx");
+    std::fs::write(&path, annotated)?;
+
+    // the text is as it was, with a comment on each repeated word.
+    pretty_assertions::assert_eq!(
+        functions::read_doc(path.clone())?
+            .only(&[definitions::Region::Body])
+            .text(),
+        functions::read_doc(PathBuf::from("../test_files/annotate.docx"))?
+            .only(&[definitions::Region::Body])
+            .text()
+    );
+    pretty_assertions::assert_eq!(
+        functions::read_doc(path)?
+            .only(&[definitions::Region::Comment])
+            .text(),
+        String::from(
+            "'frowned' also appears 8 words later\n'frowned' also appears 8 words earlier"
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn annotate_a_docx_with_a_line_break_in_its_text() -> Result<(), definitions::TonalDistanceError> {
+    let stop_words = functions::get_stop_words(
+        Some(definitions::Source::Raw(String::from(
+            "the,at,she,later,again",
+        ))),
+        false,
+    )?;
+
+    // the first paragraph's text has a line break in it, so lines and paragraphs part ways.
+    let annotated = functions::annotate_docx(
+        PathBuf::from("../test_files/annotate-newline.docx"),
+        definitions::Window::Words(50),
+        stop_words,
+        definitions::Stemmer::None,
+        0,
+    )?;
+    let path = std::env::temp_dir().join("tonal-distancing-annotated-newline.docx");
+    std::fs::write(&path, annotated)?;
+
+    pretty_assertions::assert_eq!(
+        functions::read_doc(path)?
+            .only(&[definitions::Region::Comment])
+            .text(),
+        String::from(
+            "'frowned' also appears 6 words later\n'frowned' also appears 6 words earlier"
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn sarif_report() -> Result<(), definitions::TonalDistanceError> {
+    let paths = functions::expand_paths(&[String::from("../test_files/chapters")])?;
+    let document = functions::get_document_from_files(paths, definitions::TextEncoding::Detect)?;
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Sarif,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::Str(s) => {
+            let value: serde_json::Value = serde_json::from_str(&s).unwrap();
+            pretty_assertions::assert_eq!(value["version"], "2.1.0");
+
+            // both ends of each repetition, first to last.
+            let results = value["runs"][0]["results"].as_array().unwrap();
+            pretty_assertions::assert_eq!(results.len(), 4);
+            pretty_assertions::assert_eq!(
+                results[0],
+                serde_json::json!({
+                    "ruleId": "repeated-word",
+                    "level": "note",
+                    "rank": 38,
+                    "message": { "text": "'keeper' also appears [16 words later](1)." },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "../test_files/chapters/chapter1.txt" },
+                            "region": { "startLine": 1, "startColumn": 16, "endLine": 1, "endColumn": 22 }
+                        }
+                    }],
+                    "relatedLocations": [{
+                        "id": 1,
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "../test_files/chapters/chapter10.txt" },
+                            "region": { "startLine": 1, "startColumn": 13, "endLine": 1, "endColumn": 19 }
+                        }
+                    }]
+                })
+            );
+            pretty_assertions::assert_eq!(
+                results[3]["message"]["text"],
+                "'lamp' also appears [12 words earlier](1)."
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
+
+#[test]
+fn csv_report() -> Result<(), definitions::TonalDistanceError> {
+    let paths = functions::expand_paths(&[String::from("../test_files/chapters")])?;
+    let document = functions::get_document_from_files(paths, definitions::TextEncoding::Detect)?;
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Csv,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::Str(s) => {
+            let rows: Vec<&str> = s.lines().collect();
+            pretty_assertions::assert_eq!(rows[0], "word,first_file,first_paragraph,first_line,first_column,first_position,second_file,second_paragraph,second_line,second_column,second_position,distance,severity");
+            pretty_assertions::assert_eq!(rows.len(), 3);
+            pretty_assertions::assert_eq!(
+                rows[1],
+                "keeper,../test_files/chapters/chapter1.txt,1,1,16,3,../test_files/chapters/chapter10.txt,4,1,13,19,16,38"
+            );
+        }
+        _ => panic!("Should be a string"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn csv_report_with_phrases() -> Result<(), definitions::TonalDistanceError> {
+    let res = functions::tell_you_how_bad(
+        String::from("For a moment she paused, and for a moment she did not."),
+        functions::default_stop_words(),
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            phrases: Some(4),
+            response: definitions::ResponseType::Csv,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::Str(s) => {
+            // the phrase has a row of its own, ahead of the word inside it.
+            pretty_assertions::assert_eq!(
+                s.lines().skip(1).collect::<Vec<&str>>(),
+                vec![
+                    "for a moment she,,1,1,1,1,,1,1,30,7,6,59",
+                    "moment,,1,1,7,3,,1,1,36,9,6,59"
+                ]
+            );
+        }
+        _ => panic!("Should be a string"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn csv_report_on_extracted_text() -> Result<(), definitions::TonalDistanceError> {
+    let document = functions::get_document_from_files(
+        vec![PathBuf::from("../test_files/test.md")],
+        definitions::TextEncoding::Detect,
+    )?;
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Csv,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::Str(s) => {
+            let rows: Vec<&str> = s.lines().collect();
+            // Markdown's lines aren't the text's, so the line and column are left empty.
+            pretty_assertions::assert_eq!(
+                rows[1],
+                "lighthouse,../test_files/test.md,1,,,2,../test_files/test.md,2,,,9,7,59"
+            );
+        }
+        _ => panic!("Should be a string"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sarif_report_on_extracted_text() -> Result<(), definitions::TonalDistanceError> {
+    let document = functions::get_document_from_files(
+        vec![PathBuf::from("../test_files/test.md")],
+        definitions::TextEncoding::Detect,
+    )?;
+
+    let stop_words =
+        functions::get_stop_words(Some(definitions::Source::Raw(String::from("the"))), false)?;
+
+    let res = functions::tell_you_how_bad(
+        document,
+        stop_words,
+        definitions::Settings {
+            window: definitions::Window::Words(50),
+            response: definitions::ResponseType::Sarif,
+            ..Default::default()
+        },
+    )?;
+
+    match res {
+        definitions::Response::Str(s) => {
+            let value: serde_json::Value = serde_json::from_str(&s).unwrap();
+
+            // Markdown's lines aren't the text's, so there's a paragraph but no region.
+            let results = value["runs"][0]["results"].as_array().unwrap();
+            pretty_assertions::assert_eq!(
+                results[0]["locations"],
+                serde_json::json!([{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "../test_files/test.md" }
+                    },
+                    "logicalLocations": [{ "name": "Paragraph: 1" }]
+                }])
+            );
+        }
+        _ => panic!(),
+    }
+    Ok(())
+}
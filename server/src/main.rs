@@ -9,10 +9,11 @@ use rocket::request::Request;
 use rocket::response;
 use rocket::response::{Responder, Response};
 use rocket::serde::json::{json, Value};
-use rocket::serde::Serialize;
-use serde::ser::{SerializeStruct, Serializer};
 use std::path::PathBuf;
 
+#[cfg(test)]
+mod tests;
+
 pub struct CORS;
 
 #[rocket::async_trait]
@@ -34,20 +35,6 @@ impl Fairing for CORS {
     }
 }
 
-struct Wrapper(definitions::Run);
-
-impl Serialize for Wrapper {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut s = serializer.serialize_struct("Run", 2)?;
-        s.serialize_field("text", &self.0.text)?;
-        s.serialize_field("repeated", &self.0.repeated)?;
-        s.end()
-    }
-}
-
 #[derive(Debug)]
 struct ApiResponse {
     json: Value,
@@ -68,12 +55,36 @@ fn index() -> &'static str {
     "Hello, world!"
 }
 
-#[post("/report?<lookahead>&<stop_words>", data = "<prefile>")]
-async fn report(
+/// The query parameters `/report` accepts, all optional.
+#[derive(Debug, FromForm)]
+struct ReportOptions {
     lookahead: Option<usize>,
+    window_unit: Option<String>,
     stop_words: Option<Vec<String>>,
-    mut prefile: TempFile<'_>,
-) -> ApiResponse {
+    extend_stop_words: Option<bool>,
+    stemmer: Option<String>,
+    phrases: Option<usize>,
+    min_severity: Option<u32>,
+    regions: Option<Vec<String>>,
+    per_character: Option<bool>,
+    encoding: Option<String>,
+}
+
+#[post("/report?<options..>", data = "<prefile>")]
+async fn report(options: ReportOptions, mut prefile: TempFile<'_>) -> ApiResponse {
+    let ReportOptions {
+        lookahead,
+        window_unit,
+        stop_words,
+        extend_stop_words,
+        stemmer,
+        phrases,
+        min_severity,
+        regions,
+        per_character,
+        encoding,
+    } = options;
+
     let content_type = prefile.content_type();
 
     let content_type = match content_type {
@@ -86,10 +97,29 @@ async fn report(
         }
     };
 
-    let path = if content_type.to_string() == "application/msword" {
-        PathBuf::from("/tmp/file.docx")
-    } else {
-        PathBuf::from("/tmp/file.txt")
+    let path = match content_type.to_string().as_str() {
+        "application/msword" => PathBuf::from("/tmp/file.docx"),
+        "application/vnd.oasis.opendocument.text" => PathBuf::from("/tmp/file.odt"),
+        "application/epub+zip" => PathBuf::from("/tmp/file.epub"),
+        "application/rtf" | "text/rtf" => PathBuf::from("/tmp/file.rtf"),
+        "text/markdown" => PathBuf::from("/tmp/file.md"),
+        "text/html" => PathBuf::from("/tmp/file.html"),
+        "text/x-fountain" => PathBuf::from("/tmp/file.fountain"),
+        _ => PathBuf::from("/tmp/file.txt"),
+    };
+
+    // get the encoding of a text file, detected unless we're told
+    let encoding = match encoding {
+        Some(label) => match label.parse::<definitions::TextEncoding>() {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                return ApiResponse {
+                    json: json!("Unknown encoding"),
+                    status: Status { code: 400 },
+                }
+            }
+        },
+        None => definitions::TextEncoding::Detect,
     };
 
     let res = prefile.persist_to(path.clone()).await;
@@ -100,39 +130,106 @@ async fn report(
         };
     }
 
-    let content = functions::get_content_from_file(path);
+    let content = functions::get_document_from_file(path, encoding);
     let content = match content {
         Ok(c) => c,
-        Err(_) => {
+        Err(e) => {
             return ApiResponse {
-                json: json!("Failed to get content from file"),
+                json: json!(format!("Failed to get content from file: {}", e)),
                 status: Status { code: 404 },
             };
         }
     };
 
-    println!("{}", content);
+    // get the regions to analyse, each given separately or comma-separated
+    let regions = match regions {
+        Some(names) if !names.is_empty() => {
+            let parsed = names
+                .iter()
+                .flat_map(|name| name.split(','))
+                .map(|name| name.parse::<definitions::Region>())
+                .collect::<Result<Vec<definitions::Region>, _>>();
+            match parsed {
+                Ok(parsed) => parsed,
+                Err(_) => {
+                    return ApiResponse {
+                        json: json!("Unknown region"),
+                        status: Status { code: 400 },
+                    }
+                }
+            }
+        }
+        _ => definitions::Region::DEFAULT.to_vec(),
+    };
+    let content = content.only(&regions);
+
     // get look ahead
     let lookahead = lookahead.unwrap_or(50);
 
+    // get the unit it's measured in
+    let window_unit = match window_unit {
+        Some(unit) => match unit.parse::<definitions::WindowUnit>() {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                return ApiResponse {
+                    json: json!("Unknown window unit"),
+                    status: Status { code: 400 },
+                }
+            }
+        },
+        None => definitions::WindowUnit::Words,
+    };
+
     // get stop words
     let stop_words = match stop_words {
         Some(sw) => {
             if sw.len() > 0 {
-                functions::get_stop_words(Some(definitions::Source::Raw(sw.join(""))))
+                functions::get_stop_words(
+                    Some(definitions::Source::Raw(sw.join(""))),
+                    extend_stop_words.unwrap_or(false),
+                )
             } else {
-                functions::get_stop_words(None)
+                functions::get_stop_words(None, false)
+            }
+        }
+        None => functions::get_stop_words(None, false),
+    };
+    let stop_words = match stop_words {
+        Ok(sw) => sw,
+        Err(e) => {
+            return ApiResponse {
+                json: json!(format!("Failed to load stop words: {}", e)),
+                status: Status { code: 500 },
             }
         }
-        None => functions::get_stop_words(None),
+    };
+
+    // get stemmer
+    let stemmer = match stemmer {
+        Some(st) => match st.parse::<definitions::Stemmer>() {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                return ApiResponse {
+                    json: json!("Unknown stemmer"),
+                    status: Status { code: 400 },
+                }
+            }
+        },
+        None => definitions::Stemmer::None,
     };
 
     // get our report
     let res = functions::tell_you_how_bad(
         content,
-        lookahead,
         stop_words,
-        definitions::ResponseType::Raw,
+        definitions::Settings {
+            window: definitions::Window::new(window_unit, lookahead),
+            stemmer,
+            phrases,
+            min_severity: min_severity.unwrap_or(0),
+            per_speaker: per_character.unwrap_or(false),
+            response: definitions::ResponseType::Json,
+        },
     );
 
     let res = match res {
@@ -146,17 +243,10 @@ async fn report(
     };
 
     match res {
-        definitions::Response::VecOfRuns(val) => {
-            println!("{:?}", val);
-            let wrapped = val
-                .iter()
-                .map(|word| Wrapper(word.clone()))
-                .collect::<Vec<Wrapper>>();
-            ApiResponse {
-                json: json!(wrapped),
-                status: Status { code: 200 },
-            }
-        }
+        definitions::Response::Json(val) => ApiResponse {
+            json: json!(val),
+            status: Status { code: 200 },
+        },
         _ => ApiResponse {
             json: json!("act of god"),
             status: Status { code: 500 },
@@ -164,11 +254,8 @@ async fn report(
     }
 }
 
-#[options("/report?<_lookahead>&<_stop_words>")]
-fn report_preflight(
-    _lookahead: Option<usize>,
-    _stop_words: Option<Vec<String>>,
-) -> response::status::NoContent {
+#[options("/report?<_options..>")]
+fn report_preflight(_options: ReportOptions) -> response::status::NoContent {
     response::status::NoContent
 }
 
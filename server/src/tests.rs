@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use crate::rocket;
+    use rocket::http::{ContentType, Status};
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::json;
+
+    #[test]
+    fn report_json_shape() {
+        let client = Client::tracked(rocket()).unwrap();
+        let response = client
+            .post("/report")
+            .header(ContentType::Plain)
+            .body("Snow on snow.")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let value: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        // the one stable format: tdist --response json returns the same.
+        let word = |original: &str, position: u32, start: usize, partner: u32| {
+            json!({
+                "pure_word": "snow",
+                "original_word": original,
+                "repeated": true,
+                "word_position": position,
+                "paragraph": 0,
+                "region": "body",
+                "chapter": null,
+                "speaker": null,
+                "file": null,
+                "byte_start": start,
+                "byte_end": start + 4,
+                "line": 0,
+                "column": start,
+                "partners": [{ "word_position": partner, "distance": 2, "severity": 75 }],
+                "phrase": null,
+                "severity": 75
+            })
+        };
+        let run = |text: &str, start: usize, end: usize, repeated: Option<(u32, u32)>| {
+            json!({
+                "text": text,
+                "repeated": repeated.is_some(),
+                "phrase": false,
+                "severity": if repeated.is_some() { 75 } else { 0 },
+                "byte_start": start,
+                "byte_end": end,
+                "line": 0,
+                "column": start,
+                "key": repeated.map(|_| "snow"),
+                "word_position": repeated.map(|(position, _)| position),
+                "partner_positions": repeated.map_or(vec![], |(_, partner)| vec![partner])
+            })
+        };
+        assert_eq!(
+            value,
+            json!({
+                "words": [word("Snow ", 0, 0, 2), word("snow.", 2, 8, 0)],
+                "runs": [
+                    run("Snow ", 0, 4, Some((0, 2))),
+                    run("on ", 5, 7, None),
+                    run("snow.", 8, 12, Some((2, 0)))
+                ]
+            })
+        );
+    }
+}
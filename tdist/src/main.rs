@@ -1,12 +1,13 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use library::{definitions, functions};
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 // use std::time::Instant;
 use std::fs::metadata;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-fn source_from_str(input: &str) -> definitions::Source {
+// a path if there's a file there, or else the text itself.
+fn path_or_text(input: &str) -> definitions::Source {
     let pb = PathBuf::from(input);
 
     let check = metadata(&pb);
@@ -17,14 +18,84 @@ fn source_from_str(input: &str) -> definitions::Source {
     }
 }
 
+fn source_from_str(input: &str) -> Result<definitions::Source, String> {
+    match path_or_text(input) {
+        // a typo in a file name shouldn't pass for the text itself.
+        definitions::Source::Raw(_)
+            if !input.contains(char::is_whitespace)
+                && (input.contains('.') || input.contains('/')) =>
+        {
+            Err(format!("there is no file {:?}", input))
+        }
+        source => Ok(source),
+    }
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(&['*', '?', '['][..])
+}
+
+// several files are read as one, each word knowing which it came from.
+// a single file only names itself when asked, as a report that needs a file for every word does.
+fn read_files(
+    patterns: &[String],
+    encoding: definitions::TextEncoding,
+    name_files: bool,
+) -> Result<definitions::Document> {
+    let mut paths = functions::expand_paths(patterns).context("Failed to find files")?;
+    let document = if paths.len() == 1 && !name_files {
+        functions::get_document_from_file(paths.remove(0), encoding)
+    } else {
+        functions::get_document_from_files(paths, encoding)
+    };
+    document.context("Failed to get content from file")
+}
+
+fn read_stdin(encoding: definitions::TextEncoding) -> Result<String> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        bail!("Nothing to evaluate: give a file or some text, or pipe text in");
+    }
+    let mut bytes = vec![];
+    stdin
+        .lock()
+        .read_to_end(&mut bytes)
+        .context("Failed to read from stdin")?;
+    match functions::decode_text(&bytes, encoding) {
+        Some(content) => Ok(content),
+        None => bail!("Failed to read from stdin: it doesn't look like text"),
+    }
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "tonal-distancing", about = "Look for repeated words")]
 struct Cli {
-    /// Content to evaluate. Accepts a file path or a string.
-    #[structopt(parse(from_str = source_from_str))]
-    source: definitions::Source,
+    /// Content to evaluate. Accepts a string, or file paths (.txt, .md, .html, .docx, .odt, .rtf, .epub or .fountain),
+    /// directories and globs, read as one document in natural sort order.
+    /// Reads from stdin if it's "-" or left out.
+    #[structopt(name = "source")]
+    sources: Vec<String>,
+
+    /// Files, directories or globs to evaluate, without guessing whether they're paths.
+    #[structopt(
+        short = "f",
+        long = "file",
+        name = "File",
+        number_of_values = 1,
+        conflicts_with_all = &["source", "Text"]
+    )]
+    files: Vec<String>,
+
+    /// Text to evaluate, without guessing whether it's a path.
+    #[structopt(short = "t", long = "text", name = "Text", conflicts_with = "source")]
+    text: Option<String>,
+
+    /// Optional text encoding of the source, like "windows-1252" or "utf-16le".
+    /// [default: "auto"], which reads a byte order mark, or tells UTF-8 and UTF-16 from Windows-1252.
+    #[structopt(long = "encoding", name = "Encoding")]
+    encoding: Option<definitions::TextEncoding>,
 
-    /// Set how far ahead to check
+    /// Set how far ahead to check, in words unless a window unit is given
     #[structopt(
         short = "l",
         long = "lookahead",
@@ -33,15 +104,75 @@ struct Cli {
     )]
     buffer_length: u32,
 
-    /// Optional personal stop-word list. 
+    /// Optional unit for the lookahead.
+    /// [values: "words" | "characters" | "sentences" | "paragraphs"]
+    /// [default: "words"]
+    #[structopt(
+        short = "w",
+        long = "window-unit",
+        name = "Window Unit",
+        case_insensitive = true
+    )]
+    window_unit: Option<definitions::WindowUnit>,
+
+    /// Optional personal stop-word list.
     /// Accepts a comma-separated list, or a file path to a line-separated list.
     /// If not provided, a default list is used.
-    #[structopt(short = "s", long = "stopwords", name = "Stop Words", parse(from_str = source_from_str))]
+    #[structopt(short = "s", long = "stopwords", name = "Stop Words", parse(from_str = path_or_text))]
     stop_words: Option<definitions::Source>,
 
+    /// Add the personal stop-word list to the default list, rather than replacing it.
+    #[structopt(long = "extend-stopwords", name = "Extend Stop Words")]
+    extend_stop_words: bool,
+
+    /// Print the default stop-word list and exit, as a starting point for your own.
+    #[structopt(long = "print-stopwords", name = "Print Stop Words")]
+    print_stop_words: bool,
+
+    /// Optional stemmer, so that inflected forms ("frowned", "frowning") count as repetitions.
+    /// [values: "none" | "english"]
+    /// [default: "none"]
+    #[structopt(long = "stemmer", name = "Stemmer", case_insensitive = true)]
+    stemmer: Option<definitions::Stemmer>,
+
+    /// Optional phrase detection: also look for repeated phrases of 2 up to this many words.
+    #[structopt(short = "p", long = "phrases", name = "Phrase Length")]
+    phrase_length: Option<usize>,
+
+    /// Optional minimum severity (0 to 100) for a repetition to be reported.
+    /// Rare words repeated close together score highest.
+    #[structopt(long = "min-severity", name = "Min Severity", default_value = "0")]
+    min_severity: u32,
+
+    /// Optional regions of a docx or rtf file, or a screenplay, to analyse, comma-separated.
+    /// [values: "body" | "table" | "textbox" | "header" | "footer" | "footnote" | "endnote" | "comment"
+    /// | "scene" | "action" | "character" | "dialogue" | "parenthetical" | "transition"]
+    /// [default: "body,table,textbox,footnote,endnote,action,dialogue,parenthetical"]
+    #[structopt(
+        long = "regions",
+        name = "Regions",
+        use_delimiter = true,
+        case_insensitive = true
+    )]
+    regions: Vec<definitions::Region>,
+
+    /// Check each character's dialogue against their own lines only, in a screenplay.
+    #[structopt(long = "per-character", name = "Per Character")]
+    per_character: bool,
+
+    /// Write a copy of a docx source to this path, with repeated words highlighted and commented on,
+    /// rather than a report. Only repeated words are marked, throughout the main document.
+    #[structopt(
+        long = "annotate",
+        name = "Annotated File",
+        conflicts_with_all = &["Text", "Response Type", "Regions", "Phrase Length", "Per Character"]
+    )]
+    annotate: Option<PathBuf>,
+
     /// Optional output specification.
-    /// [values: "raw" | "formatted"] 
+    /// [values: "raw" | "formatted" | "pairs" | "json" | "html" | "sarif" | "csv" | "tsv"]
     /// [default: "formatted"]
+    /// The formatted report and pairs are listed worst first.
     #[structopt(
         short = "r",
         long = "response",
@@ -50,9 +181,15 @@ struct Cli {
         case_insensitive = true
     )]
     response: Option<definitions::ResponseType>,
+
+    /// Optional colouring of the raw report, a colour for each repeated word.
+    /// [values: "always" | "never" | "auto"]
+    /// [default: "auto"], which colours it only for a terminal, and not if NO_COLOR is set.
+    #[structopt(long = "color", name = "Color", case_insensitive = true)]
+    color: Option<definitions::ColorChoice>,
 }
 
-pub fn write_report(report: definitions::Response) -> () {
+pub fn write_report(report: definitions::Response, color: definitions::ColorChoice) -> () {
     match report {
         definitions::Response::Str(s) => {
             let stdout = io::stdout();
@@ -60,10 +197,24 @@ pub fn write_report(report: definitions::Response) -> () {
             let _ = writeln!(handle, "{}", s);
             ()
         }
+        definitions::Response::VecOfPairs(v) => {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            for pair in v.iter() {
+                let _ = writeln!(handle, "{}", pair.represent());
+            }
+        }
+        definitions::Response::Json(j) => {
+            let stdout = io::stdout();
+            let mut handle = stdout.lock();
+            let _ = serde_json::to_writer_pretty(&mut handle, &j);
+            let _ = writeln!(handle);
+        }
         definitions::Response::VecOfRuns(v) => {
             // render!
-            let colorized = functions::colorize_run(v);
             let stdout = io::stdout();
+            colored::control::set_override(color.enabled(stdout.is_terminal()));
+            let colorized = functions::colorize_run(v);
             let mut handle = stdout.lock();
             let _ = writeln!(handle, "{}", colorized);
             ()
@@ -77,33 +228,92 @@ pub fn main() -> Result<()> {
 
     let args = Cli::from_args();
 
-    // get our big ol string
-    let content = match args.source {
-        definitions::Source::Pb(src) => {
-            functions::get_content_from_file(src).context("Failed to get content from file")?
+    if args.print_stop_words {
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let _ = write!(handle, "{}", functions::DEFAULT_STOP_WORDS);
+        return Ok(());
+    }
+
+    let window = definitions::Window::new(
+        args.window_unit.unwrap_or(definitions::WindowUnit::Words),
+        args.buffer_length as usize,
+    );
+    let stemmer = args.stemmer.unwrap_or(definitions::Stemmer::None);
+
+    // get our stop words
+    let stop_words = functions::get_stop_words(args.stop_words, args.extend_stop_words)
+        .context("Failed to load stop words")?;
+
+    // or mark the repetitions in a copy of a docx file, for its author to work through in Word
+    if let Some(annotated) = args.annotate {
+        let patterns = if args.files.is_empty() {
+            &args.sources
+        } else {
+            &args.files
+        };
+        let paths = functions::expand_paths(patterns).context("Failed to find files")?;
+        let source = match paths.as_slice() {
+            [path] if path.extension().is_some_and(|ext| ext == "docx") => path.to_owned(),
+            _ => bail!("--annotate needs a single docx file to annotate"),
+        };
+        let bytes =
+            functions::annotate_docx(source, window, stop_words, stemmer, args.min_severity)
+                .context("Failed to annotate the docx file")?;
+        std::fs::write(&annotated, bytes).context("Failed to write the annotated file")?;
+        return Ok(());
+    }
+
+    // get our big ol document
+    let encoding = args.encoding.unwrap_or_default();
+    let name_files = matches!(args.response, Some(definitions::ResponseType::Sarif));
+    let document: definitions::Document = if !args.files.is_empty() {
+        read_files(&args.files, encoding, name_files)?
+    } else if let Some(text) = args.text {
+        text.into()
+    } else {
+        match args.sources.as_slice() {
+            [] => read_stdin(encoding)?.into(),
+            [source] if source == "-" => read_stdin(encoding)?.into(),
+            [source] if !is_glob(source) => match source_from_str(source)
+                .map_err(|e| anyhow!("{}. Use --text to read it as text", e))?
+            {
+                definitions::Source::Pb(_) => read_files(&args.sources, encoding, name_files)?,
+                definitions::Source::Raw(text) => text.into(),
+            },
+            sources => read_files(sources, encoding, name_files)?,
         }
-        definitions::Source::Raw(src) => src,
     };
 
-    // get our stop words
-    let stop_words = functions::get_stop_words(args.stop_words);
+    // and only the parts we were asked about
+    let document = if args.regions.is_empty() {
+        document.only(&definitions::Region::DEFAULT)
+    } else {
+        document.only(&args.regions)
+    };
 
     // get our report
     let res = functions::tell_you_how_bad(
-        content,
-        args.buffer_length as usize,
+        document,
         stop_words,
-        args.response
-            .unwrap_or(definitions::ResponseType::Formatted),
+        definitions::Settings {
+            window,
+            stemmer,
+            phrases: args.phrase_length,
+            min_severity: args.min_severity,
+            per_speaker: args.per_character,
+            response: args
+                .response
+                .unwrap_or(definitions::ResponseType::Formatted),
+        },
     )
     .context("Failed to process content")?;
 
     // write report to stdout
-    write_report(res);
+    write_report(res, args.color.unwrap_or_default());
 
     // let elapsed = now.elapsed();
     // println!("Elapsed: {:.2?}", elapsed);
 
     Ok(())
 }
-